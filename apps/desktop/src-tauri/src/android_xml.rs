@@ -0,0 +1,695 @@
+//! Decoders for the compiled XML found in Android build artifacts.
+//!
+//! APKs store `AndroidManifest.xml` and `res/**/*.xml` as binary XML (AXML),
+//! app bundles (AAB) store them as aapt2 protobuf `XmlNode`s. Both are turned
+//! back into indented text so they can be read and searched.
+
+use std::collections::HashMap;
+
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_TYPE: u16 = 0x0003;
+const RES_XML_START_NAMESPACE_TYPE: u16 = 0x0100;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+const RES_XML_END_ELEMENT_TYPE: u16 = 0x0103;
+const RES_XML_CDATA_TYPE: u16 = 0x0104;
+
+const UTF8_FLAG: u32 = 1 << 8;
+const NO_INDEX: u32 = u32::MAX;
+
+/// Whether the bytes start like an AXML document
+fn is_axml(bytes: &[u8]) -> bool {
+    bytes.len() >= 8 && u16_at(bytes, 0) == Some(RES_XML_TYPE) && u16_at(bytes, 2) == Some(8)
+}
+
+/// Decode whichever compiled XML format `bytes` is in, `None` if neither applies
+pub fn decode(bytes: &[u8]) -> Option<String> {
+    if is_axml(bytes) {
+        decode_axml(bytes)
+    } else {
+        decode_proto(bytes)
+    }
+}
+
+/// Indented XML text built from start/end events
+struct XmlWriter {
+    out: String,
+    depth: usize,
+    open: bool, // the last start tag still lacks its `>`
+}
+
+impl XmlWriter {
+    fn new() -> Self {
+        Self {
+            out: String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n"),
+            depth: 0,
+            open: false,
+        }
+    }
+
+    fn indent(&mut self, depth: usize) {
+        self.out.extend(std::iter::repeat("    ").take(depth));
+    }
+
+    fn close_start_tag(&mut self) {
+        if self.open {
+            self.out.push_str(">\n");
+            self.open = false;
+        }
+    }
+
+    /// One attribute per line, the way Android Studio shows manifests
+    fn start(&mut self, name: &str, attributes: &[(String, String)]) {
+        self.close_start_tag();
+        self.indent(self.depth);
+        self.out.push('<');
+        self.out.push_str(name);
+        for (name, value) in attributes {
+            self.out.push('\n');
+            self.indent(self.depth + 1);
+            self.out
+                .push_str(&format!("{}=\"{}\"", name, escape(value)));
+        }
+        self.open = true;
+        self.depth += 1;
+    }
+
+    fn text(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        self.close_start_tag();
+        self.indent(self.depth);
+        self.out.push_str(&escape(text));
+        self.out.push('\n');
+    }
+
+    fn end(&mut self, name: &str) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.open {
+            self.out.push_str(" />\n");
+            self.open = false;
+        } else {
+            self.indent(self.depth);
+            self.out.push_str(&format!("</{}>\n", name));
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `name` prefixed with the namespace prefix declared for `uri`, if any
+fn qualified(name: &str, uri: Option<&str>, prefixes: &HashMap<String, String>) -> String {
+    match uri.and_then(|u| prefixes.get(u)) {
+        Some(prefix) if !prefix.is_empty() => format!("{}:{}", prefix, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Format a typed resource value (`Res_value` in AXML, `Primitive` in protobuf)
+fn typed_value(data_type: u8, data: u32) -> String {
+    const UNITS: [&str; 6] = ["px", "dp", "sp", "pt", "in", "mm"];
+    match data_type {
+        0x01 if data == 0 => "@null".to_string(),
+        0x01 => format!("@0x{:08x}", data),
+        0x02 => format!("?0x{:08x}", data),
+        0x04 => f32::from_bits(data).to_string(),
+        0x05 => {
+            let unit = UNITS.get((data & 0xf) as usize).copied().unwrap_or("");
+            format!("{}{}", complex_value(data), unit)
+        }
+        0x06 => {
+            let unit = if data & 0xf == 0 { "%" } else { "%p" };
+            format!("{}{}", complex_value(data) * 100.0, unit)
+        }
+        0x10 => (data as i32).to_string(),
+        0x11 => format!("0x{:x}", data),
+        0x12 => (data != 0).to_string(),
+        0x1c | 0x1e => format!("#{:08x}", data),
+        0x1d | 0x1f => format!("#{:06x}", data & 0xff_ffff),
+        _ => format!("0x{:08x}", data),
+    }
+}
+
+/// Value of a dimension or fraction: a 24-bit mantissa with a radix
+fn complex_value(data: u32) -> f32 {
+    const RADIX: [f32; 4] = [
+        1.0 / (1u32 << 8) as f32,
+        1.0 / (1u32 << 15) as f32,
+        1.0 / (1u32 << 23) as f32,
+        1.0 / (1u32 << 31) as f32,
+    ];
+    ((data & 0xffff_ff00) as i32) as f32 * RADIX[((data >> 4) & 3) as usize]
+}
+
+fn u16_at(bytes: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(pos..pos + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(bytes: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(pos..pos + 4)?.try_into().ok()?,
+    ))
+}
+
+/// The string pool chunk of an AXML document
+fn string_pool(chunk: &[u8]) -> Option<Vec<String>> {
+    let header_size = u16_at(chunk, 2)? as usize;
+    let count = u32_at(chunk, 8)? as usize;
+    let flags = u32_at(chunk, 16)?;
+    let strings_start = u32_at(chunk, 20)? as usize;
+    let utf8 = flags & UTF8_FLAG != 0;
+
+    let mut strings = Vec::with_capacity(count.min(chunk.len() / 4));
+    for i in 0..count {
+        let offset = strings_start + u32_at(chunk, header_size + i * 4)? as usize;
+        strings.push(if utf8 {
+            pool_utf8(chunk, offset)?
+        } else {
+            pool_utf16(chunk, offset)?
+        });
+    }
+    Some(strings)
+}
+
+/// UTF-8 pool entry: length in UTF-16 units, length in bytes, bytes
+fn pool_utf8(chunk: &[u8], mut pos: usize) -> Option<String> {
+    let mut length = || {
+        let first = *chunk.get(pos)? as usize;
+        pos += 1;
+        if first & 0x80 == 0 {
+            return Some(first);
+        }
+        let second = *chunk.get(pos)? as usize;
+        pos += 1;
+        Some(((first & 0x7f) << 8) | second)
+    };
+    length()?;
+    let len = length()?;
+    Some(String::from_utf8_lossy(chunk.get(pos..pos + len)?).to_string())
+}
+
+/// UTF-16 pool entry: length in units, then the units
+fn pool_utf16(chunk: &[u8], pos: usize) -> Option<String> {
+    let mut len = u16_at(chunk, pos)? as usize;
+    let mut start = pos + 2;
+    if len & 0x8000 != 0 {
+        len = ((len & 0x7fff) << 16) | u16_at(chunk, start)? as usize;
+        start += 2;
+    }
+    let units: Vec<u16> = (0..len)
+        .map(|i| u16_at(chunk, start + i * 2))
+        .collect::<Option<_>>()?;
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Decode an AXML document into XML text
+fn decode_axml(bytes: &[u8]) -> Option<String> {
+    if !is_axml(bytes) {
+        return None;
+    }
+
+    let mut strings = Vec::new();
+    let string = |strings: &[String], index: u32| -> Option<String> {
+        (index != NO_INDEX).then(|| strings.get(index as usize).cloned().unwrap_or_default())
+    };
+    let mut prefixes: HashMap<String, String> = HashMap::new();
+    let mut pending_namespaces = Vec::new();
+    let mut writer = XmlWriter::new();
+
+    let end = (u32_at(bytes, 4)? as usize).min(bytes.len());
+    let mut pos = 8;
+    while pos + 8 <= end {
+        let chunk_type = u16_at(bytes, pos)?;
+        let header_size = u16_at(bytes, pos + 2)? as usize;
+        let size = u32_at(bytes, pos + 4)? as usize;
+        if size < 8 || pos + size > end {
+            return None;
+        }
+        let chunk = &bytes[pos..pos + size];
+        let ext = header_size;
+
+        match chunk_type {
+            RES_STRING_POOL_TYPE => strings = string_pool(chunk)?,
+            RES_XML_START_NAMESPACE_TYPE => {
+                let prefix = string(&strings, u32_at(chunk, ext)?).unwrap_or_default();
+                let uri = string(&strings, u32_at(chunk, ext + 4)?).unwrap_or_default();
+                prefixes.insert(uri.clone(), prefix.clone());
+                pending_namespaces.push((format!("xmlns:{}", prefix), uri));
+            }
+            RES_XML_START_ELEMENT_TYPE => {
+                let ns = string(&strings, u32_at(chunk, ext)?);
+                let name = string(&strings, u32_at(chunk, ext + 4)?).unwrap_or_default();
+                let attribute_start = u16_at(chunk, ext + 8)? as usize;
+                let attribute_size = u16_at(chunk, ext + 10)? as usize;
+                let attribute_count = u16_at(chunk, ext + 12)? as usize;
+
+                let mut attributes = std::mem::take(&mut pending_namespaces);
+                for i in 0..attribute_count {
+                    let at = ext + attribute_start + i * attribute_size;
+                    let attr_ns = string(&strings, u32_at(chunk, at)?);
+                    let attr_name = string(&strings, u32_at(chunk, at + 4)?).unwrap_or_default();
+                    let raw = string(&strings, u32_at(chunk, at + 8)?);
+                    let data_type = *chunk.get(at + 15)?;
+                    let data = u32_at(chunk, at + 16)?;
+                    let value = match raw {
+                        Some(raw) => raw,
+                        None if data_type == 0x03 => string(&strings, data).unwrap_or_default(),
+                        None => typed_value(data_type, data),
+                    };
+                    attributes.push((qualified(&attr_name, attr_ns.as_deref(), &prefixes), value));
+                }
+                writer.start(&qualified(&name, ns.as_deref(), &prefixes), &attributes);
+            }
+            RES_XML_END_ELEMENT_TYPE => {
+                let ns = string(&strings, u32_at(chunk, ext)?);
+                let name = string(&strings, u32_at(chunk, ext + 4)?).unwrap_or_default();
+                writer.end(&qualified(&name, ns.as_deref(), &prefixes));
+            }
+            RES_XML_CDATA_TYPE => {
+                if let Some(text) = string(&strings, u32_at(chunk, ext)?) {
+                    writer.text(&text);
+                }
+            }
+            _ => {} // resource map, end namespace
+        }
+        pos += size;
+    }
+    Some(writer.out)
+}
+
+/// Minimal protobuf wire-format reader
+struct Proto<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+/// A field value as read off the wire
+enum Field<'a> {
+    Varint(u64),
+    Fixed32(u32),
+    Bytes(&'a [u8]),
+}
+
+impl<'a> Proto<'a> {
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.pos)?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+
+    fn field(&mut self) -> Option<(u64, Field<'a>)> {
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Field::Varint(self.varint()?),
+            1 => Field::Bytes(self.take(8)?),
+            2 => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => Field::Fixed32(u32::from_le_bytes(self.take(4)?.try_into().ok()?)),
+            _ => return None,
+        };
+        Some((key >> 3, value))
+    }
+
+    /// Every field of a message in wire order, `None` if it is malformed
+    fn fields(bytes: &'a [u8]) -> Option<Vec<(u64, Field<'a>)>> {
+        let mut proto = Proto { bytes, pos: 0 };
+        let mut fields = Vec::new();
+        while proto.pos < bytes.len() {
+            fields.push(proto.field()?);
+        }
+        Some(fields)
+    }
+}
+
+fn proto_string(bytes: &[u8]) -> Option<String> {
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Decode an aapt2 protobuf `XmlNode` into XML text
+fn decode_proto(bytes: &[u8]) -> Option<String> {
+    // An XmlNode document always starts with its `element` field
+    if bytes.first() != Some(&0x0a) {
+        return None;
+    }
+    let mut writer = XmlWriter::new();
+    proto_node(bytes, &mut writer, &mut HashMap::new(), 0)?;
+    Some(writer.out)
+}
+
+/// XmlNode: element = 1, text = 2
+fn proto_node(
+    bytes: &[u8],
+    writer: &mut XmlWriter,
+    prefixes: &mut HashMap<String, String>,
+    depth: usize,
+) -> Option<()> {
+    if depth > 256 {
+        return None;
+    }
+    for (number, field) in Proto::fields(bytes)? {
+        match (number, field) {
+            (1, Field::Bytes(element)) => proto_element(element, writer, prefixes, depth)?,
+            (2, Field::Bytes(text)) => writer.text(&proto_string(text)?),
+            _ => {}
+        }
+    }
+    Some(())
+}
+
+/// XmlElement: namespace_declaration = 1, namespace_uri = 2, name = 3,
+/// attribute = 4, child = 5
+fn proto_element(
+    bytes: &[u8],
+    writer: &mut XmlWriter,
+    prefixes: &mut HashMap<String, String>,
+    depth: usize,
+) -> Option<()> {
+    let mut attributes = Vec::new();
+    let mut children = Vec::new();
+    let mut raw_attributes = Vec::new();
+    let mut uri = None;
+    let mut name = String::new();
+
+    for (number, field) in Proto::fields(bytes)? {
+        let Field::Bytes(value) = field else {
+            continue;
+        };
+        match number {
+            1 => {
+                let mut prefix = String::new();
+                let mut ns_uri = String::new();
+                for (n, f) in Proto::fields(value)? {
+                    match (n, f) {
+                        (1, Field::Bytes(b)) => prefix = proto_string(b)?,
+                        (2, Field::Bytes(b)) => ns_uri = proto_string(b)?,
+                        _ => {}
+                    }
+                }
+                prefixes.insert(ns_uri.clone(), prefix.clone());
+                attributes.push((format!("xmlns:{}", prefix), ns_uri));
+            }
+            2 => uri = Some(proto_string(value)?).filter(|u| !u.is_empty()),
+            3 => name = proto_string(value)?,
+            4 => raw_attributes.push(value),
+            5 => children.push(value),
+            _ => {}
+        }
+    }
+
+    for raw in raw_attributes {
+        attributes.push(proto_attribute(raw, prefixes)?);
+    }
+    let name = qualified(&name, uri.as_deref(), prefixes);
+    writer.start(&name, &attributes);
+    for child in children {
+        proto_node(child, writer, prefixes, depth + 1)?;
+    }
+    writer.end(&name);
+    Some(())
+}
+
+/// XmlAttribute: namespace_uri = 1, name = 2, value = 3, compiled_item = 6
+fn proto_attribute(bytes: &[u8], prefixes: &HashMap<String, String>) -> Option<(String, String)> {
+    let mut uri = None;
+    let mut name = String::new();
+    let mut value = String::new();
+    let mut compiled = None;
+    for (number, field) in Proto::fields(bytes)? {
+        match (number, field) {
+            (1, Field::Bytes(b)) => uri = Some(proto_string(b)?).filter(|u| !u.is_empty()),
+            (2, Field::Bytes(b)) => name = proto_string(b)?,
+            (3, Field::Bytes(b)) => value = proto_string(b)?,
+            (6, Field::Bytes(b)) => compiled = Some(b),
+            _ => {}
+        }
+    }
+    if value.is_empty() {
+        if let Some(item) = compiled {
+            value = proto_item(item).unwrap_or_default();
+        }
+    }
+    Some((qualified(&name, uri.as_deref(), prefixes), value))
+}
+
+/// Item: ref = 1, str = 2, raw_str = 3, prim = 7
+fn proto_item(bytes: &[u8]) -> Option<String> {
+    for (number, field) in Proto::fields(bytes)? {
+        let Field::Bytes(value) = field else {
+            continue;
+        };
+        match number {
+            1 => {
+                // Reference: id = 2, name = 3
+                let mut id = 0;
+                let mut ref_name = None;
+                for (n, f) in Proto::fields(value)? {
+                    match (n, f) {
+                        (2, Field::Varint(v)) => id = v as u32,
+                        (3, Field::Bytes(b)) => ref_name = proto_string(b),
+                        _ => {}
+                    }
+                }
+                return Some(match ref_name.filter(|n| !n.is_empty()) {
+                    Some(ref_name) => format!("@{}", ref_name),
+                    None => typed_value(0x01, id),
+                });
+            }
+            2 | 3 => {
+                // String and RawString: value = 1
+                for (n, f) in Proto::fields(value)? {
+                    if let (1, Field::Bytes(b)) = (n, f) {
+                        return proto_string(b);
+                    }
+                }
+                return Some(String::new());
+            }
+            7 => return proto_primitive(value),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Primitive, mapped onto the matching `Res_value` data type
+fn proto_primitive(bytes: &[u8]) -> Option<String> {
+    let (number, field) = Proto::fields(bytes)?.into_iter().next()?;
+    let data = match field {
+        Field::Varint(v) => v as u32,
+        Field::Fixed32(v) => v,
+        Field::Bytes(_) => return None,
+    };
+    let data_type = match number {
+        1 => return Some("@null".to_string()),
+        2 => return Some("@empty".to_string()),
+        3 => 0x04,
+        4 | 13 => 0x05,
+        5 | 14 => 0x06,
+        6 => 0x10,
+        7 => 0x11,
+        8 => 0x12,
+        9 => 0x1c,
+        10 => 0x1d,
+        11 => 0x1e,
+        12 => 0x1f,
+        _ => return None,
+    };
+    Some(typed_value(data_type, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANDROID_URI: &str = "http://schemas.android.com/apk/res/android";
+
+    fn chunk(chunk_type: u16, header: &[u8], body: &[u8]) -> Vec<u8> {
+        let header_size = 8 + header.len();
+        let mut out = Vec::new();
+        out.extend(chunk_type.to_le_bytes());
+        out.extend((header_size as u16).to_le_bytes());
+        out.extend(((header_size + body.len()) as u32).to_le_bytes());
+        out.extend(header);
+        out.extend(body);
+        out
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn utf8_pool(strings: &[&str]) -> Vec<u8> {
+        let mut offsets = Vec::new();
+        let mut data = Vec::new();
+        for s in strings {
+            offsets.push(data.len() as u32);
+            data.extend([s.len() as u8, s.len() as u8]);
+            data.extend(s.as_bytes());
+            data.push(0);
+        }
+        let strings_start = 28 + offsets.len() as u32 * 4;
+        let header = words(&[strings.len() as u32, 0, UTF8_FLAG, strings_start, 0]);
+        let mut body = words(&offsets);
+        body.extend(data);
+        chunk(RES_STRING_POOL_TYPE, &header, &body)
+    }
+
+    /// `<manifest xmlns:android=".." package="com.example" android:versionCode="3" />`
+    fn manifest_axml() -> Vec<u8> {
+        let pool = utf8_pool(&[
+            "android",
+            ANDROID_URI,
+            "manifest",
+            "package",
+            "com.example",
+            "versionCode",
+        ]);
+        let node_header = words(&[1, NO_INDEX]);
+        let namespace = chunk(RES_XML_START_NAMESPACE_TYPE, &node_header, &words(&[0, 1]));
+
+        let mut element = words(&[NO_INDEX, 2]);
+        element.extend([20u16, 20, 2, 0, 0, 0].iter().flat_map(|v| v.to_le_bytes()));
+        // Attribute: namespace, name, raw value, then Res_value (size, res0, type, data)
+        element.extend(words(&[NO_INDEX, 3, 4]));
+        element.extend([8, 0, 0, 0x03]);
+        element.extend(words(&[4]));
+        element.extend(words(&[1, 5, NO_INDEX]));
+        element.extend([8, 0, 0, 0x10]);
+        element.extend(words(&[3]));
+        let start = chunk(RES_XML_START_ELEMENT_TYPE, &node_header, &element);
+        let end = chunk(
+            RES_XML_END_ELEMENT_TYPE,
+            &node_header,
+            &words(&[NO_INDEX, 2]),
+        );
+
+        let body = [pool, namespace, start, end].concat();
+        chunk(RES_XML_TYPE, &[], &body)
+    }
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+        out
+    }
+
+    fn message(number: u64, bytes: &[u8]) -> Vec<u8> {
+        let mut out = varint(number << 3 | 2);
+        out.extend(varint(bytes.len() as u64));
+        out.extend(bytes);
+        out
+    }
+
+    /// The manifest above as an aapt2 `XmlNode`, with an `<application>` child
+    fn manifest_proto() -> Vec<u8> {
+        let namespace = [message(1, b"android"), message(2, ANDROID_URI.as_bytes())].concat();
+        let version = [varint(6 << 3), varint(3)].concat();
+        let version_code = [
+            message(1, ANDROID_URI.as_bytes()),
+            message(2, b"versionCode"),
+            message(6, &message(7, &version)),
+        ]
+        .concat();
+        let package = [message(2, b"package"), message(3, b"com.example")].concat();
+        let child = message(1, &message(3, b"application"));
+        let element = [
+            message(1, &namespace),
+            message(3, b"manifest"),
+            message(4, &package),
+            message(4, &version_code),
+            message(5, &child),
+        ]
+        .concat();
+        message(1, &element)
+    }
+
+    #[test]
+    fn decodes_binary_xml() {
+        let text = decode(&manifest_axml()).unwrap();
+        assert_eq!(
+            text,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <manifest\n    \
+             xmlns:android=\"http://schemas.android.com/apk/res/android\"\n    \
+             package=\"com.example\"\n    \
+             android:versionCode=\"3\" />\n"
+        );
+    }
+
+    #[test]
+    fn decodes_protobuf_xml() {
+        let text = decode(&manifest_proto()).unwrap();
+        assert!(text.contains("<manifest\n"));
+        assert!(text.contains("    xmlns:android=\"http://schemas.android.com/apk/res/android\"\n"));
+        assert!(text.contains("    package=\"com.example\"\n"));
+        assert!(text.contains("    android:versionCode=\"3\">\n"));
+        assert!(text.contains("    <application />\n</manifest>\n"));
+    }
+
+    #[test]
+    fn truncated_documents_are_rejected() {
+        let axml = manifest_axml();
+        // Cutting into the string pool leaves an incomplete chunk
+        assert_eq!(decode(&axml[..40]), None);
+        for len in 0..axml.len() {
+            decode(&axml[..len]);
+        }
+
+        let proto = manifest_proto();
+        for len in 0..proto.len() {
+            assert_eq!(decode(&proto[..len]), None, "prefix of {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn malformed_documents_are_rejected() {
+        // A string pool claiming more strings than it holds
+        let mut axml = manifest_axml();
+        axml[16..20].copy_from_slice(&1000u32.to_le_bytes());
+        assert_eq!(decode(&axml), None);
+
+        // A chunk larger than the document
+        let mut axml = manifest_axml();
+        axml[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&axml), None);
+
+        // Unknown wire type, and a length running past the end
+        assert_eq!(decode(&[0x0a, 0x02, 0x0b, 0x00]), None);
+        assert_eq!(decode(&[0x0a, 0xff, 0xff, 0xff, 0xff, 0x0f]), None);
+
+        // Nesting deep enough to exhaust the stack
+        let mut node = message(1, &message(3, b"a"));
+        for _ in 0..300 {
+            node = message(1, &[message(3, b"a"), message(5, &node)].concat());
+        }
+        assert_eq!(decode(&node), None);
+
+        assert_eq!(decode(b"plain text"), None);
+    }
+}
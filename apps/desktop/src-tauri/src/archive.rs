@@ -0,0 +1,273 @@
+//! Read-only access to the entries of zip-based archives such as APKs, app
+//! bundles, JARs and zipped `.app` bundles.
+//!
+//! An entry is addressed as `<archive>!/<entry>`, for example
+//! `app-release.apk!/AndroidManifest.xml`.
+
+use crate::android_xml;
+use crate::errors::{AppError, AppResult};
+use encoding_rs::Encoding;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// Separates the archive path from the entry path
+pub const ENTRY_SEPARATOR: &str = "!/";
+
+const ARCHIVE_EXTENSIONS: &[&str] = &["apk", "aab", "apks", "xapk", "aar", "jar", "ipa", "zip"];
+
+/// Larger entries are not decompressed
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Whether `path` names a zip-based archive, judging by its extension
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| ARCHIVE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Split `app.apk!/res/layout/main.xml` into the archive and the entry path.
+///
+/// `None` for plain paths, including ones that merely contain `!/` in a
+/// folder name.
+pub fn split(path: &str) -> Option<(&str, &str)> {
+    let mut from = 0;
+    while let Some(i) = path[from..].find(ENTRY_SEPARATOR) {
+        let (archive, entry) = (&path[..from + i], &path[from + i + ENTRY_SEPARATOR.len()..]);
+        if is_archive(Path::new(archive)) {
+            return Some((archive, entry.trim_end_matches('/')));
+        }
+        from += i + ENTRY_SEPARATOR.len();
+    }
+    None
+}
+
+/// The path shown for an entry, the inverse of `split`
+pub fn entry_path(archive: &Path, entry: &str) -> String {
+    format!("{}{}{}", archive.to_string_lossy(), ENTRY_SEPARATOR, entry)
+}
+
+fn zip_error(archive: &Path, err: ZipError) -> AppError {
+    match err {
+        ZipError::Io(e) => e.into(),
+        e => AppError::NotSupported(format!("Cannot read {}: {}", archive.display(), e)),
+    }
+}
+
+fn open(archive: &Path) -> AppResult<ZipArchive<BufReader<File>>> {
+    let file = File::open(archive)?;
+    ZipArchive::new(BufReader::new(file)).map_err(|e| zip_error(archive, e))
+}
+
+/// Compiled XML (binary AXML in APKs, protobuf in app bundles) becomes text,
+/// anything else is returned as stored
+fn decode_entry(name: &str, bytes: Vec<u8>) -> Vec<u8> {
+    // Protobuf XML has too few control bytes for `is_binary`, so look for plain text XML instead
+    let plain = bytes
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|&b| b == b'<' || Encoding::for_bom(&bytes).is_some());
+    if !name.ends_with(".xml") || plain {
+        return bytes;
+    }
+    android_xml::decode(&bytes).map_or(bytes, String::into_bytes)
+}
+
+fn read_entry_bytes(file: impl Read, size: u64) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(size.min(MAX_ENTRY_SIZE) as usize);
+    // The declared size is not trusted, a crafted archive could inflate far beyond it
+    file.take(MAX_ENTRY_SIZE).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Read one entry, with compiled XML decoded to text
+pub fn read_entry(archive: &Path, entry: &str) -> AppResult<Vec<u8>> {
+    let mut zip = open(archive)?;
+    let file = zip.by_name(entry).map_err(|e| match e {
+        ZipError::FileNotFound => {
+            AppError::NotFound(format!("No entry {} in {}", entry, archive.display()))
+        }
+        e => zip_error(archive, e),
+    })?;
+    if file.is_dir() {
+        return Err(AppError::NotSupported(format!(
+            "{} is a folder",
+            entry_path(archive, entry)
+        )));
+    }
+    if file.size() > MAX_ENTRY_SIZE {
+        return Err(AppError::NotSupported(format!(
+            "{} is too large to read ({} bytes)",
+            entry_path(archive, entry),
+            file.size()
+        )));
+    }
+
+    let size = file.size();
+    let bytes = read_entry_bytes(file, size)?;
+    Ok(decode_entry(entry, bytes))
+}
+
+/// Call `visit` with the name and decoded contents of every file entry that
+/// `wanted` accepts, until `visit` returns `false`.
+///
+/// Entries that cannot be read or are too large are skipped.
+pub fn for_each_entry<W, V>(archive: &Path, wanted: W, mut visit: V) -> AppResult<()>
+where
+    W: Fn(&str) -> bool,
+    V: FnMut(&str, Vec<u8>) -> bool,
+{
+    let mut zip = open(archive)?;
+    for i in 0..zip.len() {
+        let Ok(file) = zip.by_index(i) else {
+            continue;
+        };
+        if file.is_dir() || file.size() > MAX_ENTRY_SIZE || !wanted(file.name()) {
+            continue;
+        }
+
+        let name = file.name().to_string();
+        let size = file.size();
+        let Ok(bytes) = read_entry_bytes(file, size) else {
+            continue;
+        };
+        if !visit(&name, decode_entry(&name, bytes)) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        zip.add_directory("res/", SimpleFileOptions::default())
+            .unwrap();
+        for (name, bytes) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn splits_entry_paths() {
+        assert_eq!(
+            split("/p/app.apk!/res/layout/main.xml"),
+            Some(("/p/app.apk", "res/layout/main.xml"))
+        );
+        assert_eq!(split("/p/App.APK!/res/"), Some(("/p/App.APK", "res")));
+        // `!/` in a folder name that is not an archive
+        assert_eq!(
+            split("/p/wow!/app.jar!/a.class"),
+            Some(("/p/wow!/app.jar", "a.class"))
+        );
+        assert_eq!(split("/p/wow!/notes.txt"), None);
+        assert_eq!(split("/p/app.apk"), None);
+        assert_eq!(
+            entry_path(Path::new("/p/app.apk"), "res/main.xml"),
+            "/p/app.apk!/res/main.xml"
+        );
+    }
+
+    #[test]
+    fn reads_and_visits_entries() {
+        let dir = temp_dir("archive-entries");
+        let apk = dir.join("app.apk");
+        write_zip(&apk, &[("assets/a.txt", b"hello"), ("res/b.xml", b"<b/>")]);
+
+        assert_eq!(read_entry(&apk, "assets/a.txt").unwrap(), b"hello");
+        assert_eq!(read_entry(&apk, "res/b.xml").unwrap(), b"<b/>");
+        assert!(matches!(
+            read_entry(&apk, "missing"),
+            Err(AppError::NotFound(_))
+        ));
+        assert!(matches!(
+            read_entry(&apk, "res/"),
+            Err(AppError::NotSupported(_))
+        ));
+
+        let mut visited = Vec::new();
+        for_each_entry(
+            &apk,
+            |name| name.starts_with("assets/"),
+            |name, bytes| {
+                visited.push((name.to_string(), bytes));
+                true
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            visited,
+            vec![("assets/a.txt".to_string(), b"hello".to_vec())]
+        );
+
+        let mut count = 0;
+        for_each_entry(
+            &apk,
+            |_| true,
+            |_, _| {
+                count += 1;
+                false
+            },
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn truncated_and_malformed_archives_are_errors() {
+        let dir = temp_dir("archive-broken");
+        let apk = dir.join("app.apk");
+        write_zip(&apk, &[("assets/a.txt", b"hello")]);
+        let bytes = std::fs::read(&apk).unwrap();
+
+        // Without the central directory at the end the archive cannot be opened
+        let truncated = dir.join("truncated.apk");
+        std::fs::write(&truncated, &bytes[..bytes.len() - 10]).unwrap();
+        assert!(read_entry(&truncated, "assets/a.txt").is_err());
+        assert!(for_each_entry(&truncated, |_| true, |_, _| true).is_err());
+
+        let garbage = dir.join("garbage.apk");
+        std::fs::write(&garbage, b"PK\x03\x04 not really a zip").unwrap();
+        assert!(matches!(
+            read_entry(&garbage, "a"),
+            Err(AppError::NotSupported(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn entries_are_capped_whatever_size_they_declare() {
+        let bytes = read_entry_bytes(std::io::repeat(0).take(MAX_ENTRY_SIZE + 10), 1).unwrap();
+        assert_eq!(bytes.len() as u64, MAX_ENTRY_SIZE);
+    }
+
+    #[test]
+    fn only_compiled_xml_is_decoded() {
+        assert_eq!(decode_entry("a.xml", b"  <a/>".to_vec()), b"  <a/>");
+        assert_eq!(decode_entry("a.bin", vec![0x0a, 0x01]), vec![0x0a, 0x01]);
+        // Not a compiled document after all, kept as stored
+        assert_eq!(decode_entry("a.xml", vec![0x0a, 0xff]), vec![0x0a, 0xff]);
+        let proto = [0x0a, 0x03, 0x1a, 0x01, b'a'];
+        assert_eq!(
+            String::from_utf8(decode_entry("a.xml", proto.to_vec())).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<a />\n"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use crate::commands::search::walk_workspace;
+use crate::file_io;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Folders assets are served or bundled from, matched anywhere in the tree
+/// so monorepo packages are covered too
+const ASSET_DIRS: &[&str] = &[
+    "static",
+    "public",
+    "src/assets",
+    "src/lib/assets",
+    "src-tauri/icons",
+];
+
+/// Files that can reference an asset by path
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "ts", "tsx", "js", "jsx", "mjs", "svelte", "vue", "html", "css", "scss", "sass", "less", "rs",
+    "json", "toml", "md",
+];
+
+/// Larger sources (bundles, lock files) are not scanned for references
+const MAX_SOURCE_SIZE: u64 = 1024 * 1024;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Asset {
+    path: String,
+    relative: String, // to the workspace root, with forward slashes
+    kind: String,     // image, svg, font, audio or video
+    format: String,   // lowercase extension
+    size: u64,
+    hash: String, // SHA-256, equal for duplicated files
+    width: Option<u32>,
+    height: Option<u32>,
+    references: Vec<String>, // source files that mention the asset, empty if unused
+}
+
+/// Asset kind of a file extension, `None` for anything that is not an asset
+fn kind_of(ext: &str) -> Option<&'static str> {
+    Some(match ext {
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "bmp" | "ico" | "icns" | "tif"
+        | "tiff" => "image",
+        "svg" => "svg",
+        "ttf" | "otf" | "woff" | "woff2" | "eot" => "font",
+        "mp3" | "wav" | "ogg" | "flac" | "aac" | "m4a" | "opus" => "audio",
+        "mp4" | "webm" | "mov" | "mkv" | "avi" | "m4v" => "video",
+        _ => return None,
+    })
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Path of `relative` below the asset folder containing it, `None` outside asset folders.
+///
+/// This is how the file is usually referenced: `static/img/logo.png` is
+/// served as `/img/logo.png`.
+fn asset_path(relative: &str) -> Option<&str> {
+    let with_slash = format!("/{}", relative);
+    ASSET_DIRS
+        .iter()
+        .filter_map(|dir| {
+            let marker = format!("/{}/", dir);
+            with_slash.find(&marker).map(|i| i + marker.len() - 1)
+        })
+        .min()
+        .map(|start| &relative[start..])
+}
+
+/// Width and height of an SVG from its `width`/`height` attributes or its `viewBox`
+fn svg_dimensions(path: &Path) -> Option<(u32, u32)> {
+    static SVG_TAG: OnceLock<Regex> = OnceLock::new();
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    let svg_tag = SVG_TAG.get_or_init(|| Regex::new(r"(?s)<svg\b[^>]*>").unwrap());
+    let attribute = ATTRIBUTE
+        .get_or_init(|| Regex::new(r#"\b(width|height|viewBox)\s*=\s*["']([^"']*)["']"#).unwrap());
+
+    let source = fs::read_to_string(path).ok()?;
+    let tag = svg_tag.find(&source)?.as_str();
+    let mut size = (None, None);
+    let mut view_box = None;
+    for caps in attribute.captures_iter(tag) {
+        // Percentages and em sizes say nothing about the drawing itself
+        let length = || caps[2].trim().trim_end_matches("px").parse::<f32>().ok();
+        match &caps[1] {
+            "width" => size.0 = length(),
+            "height" => size.1 = length(),
+            _ => {
+                let numbers: Vec<f32> = caps[2]
+                    .split([' ', ','])
+                    .filter_map(|n| n.parse().ok())
+                    .collect();
+                if let [_, _, w, h] = numbers[..] {
+                    view_box = Some((w, h));
+                }
+            }
+        }
+    }
+
+    let (width, height) = match size {
+        (Some(w), Some(h)) => (w, h),
+        _ => view_box?,
+    };
+    Some((width.round() as u32, height.round() as u32))
+}
+
+fn dimensions(path: &Path, kind: &str) -> Option<(u32, u32)> {
+    match kind {
+        "image" => image::image_dimensions(path).ok(),
+        "svg" => svg_dimensions(path),
+        _ => None,
+    }
+}
+
+/// Whether `source` contains `needle` as a whole path, so `icon.png` is not
+/// found inside `app-icon.png`
+fn mentions(source: &str, needle: &str) -> bool {
+    source.match_indices(needle).any(|(i, _)| {
+        source[..i].chars().next_back().map_or(true, |c| {
+            !c.is_alphanumeric() && !matches!(c, '-' | '_' | '.')
+        })
+    })
+}
+
+/// Raster images in the asset folders of `roots`
+pub fn images(roots: &[PathBuf], skip: &[String]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for root in roots {
+        for entry in walk_workspace(root, skip, false).filter_map(|e| e.ok()) {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let path = entry.into_path();
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if kind_of(&extension(&path)) == Some("image") && asset_path(&relative).is_some() {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+/// List the images, fonts, audio, video and SVGs in the asset folders of
+/// `roots`, with the source files that reference each of them.
+pub fn scan(roots: &[PathBuf], skip: &[String]) -> Vec<Asset> {
+    let mut found = Vec::new();
+    let mut sources = Vec::new();
+
+    for root in roots {
+        for entry in walk_workspace(root, skip, false).filter_map(|e| e.ok()) {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let path = entry.into_path();
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let ext = extension(&path);
+
+            match kind_of(&ext) {
+                Some(kind) if asset_path(&relative).is_some() => found.push((path, relative, kind)),
+                _ if SOURCE_EXTENSIONS.contains(&ext.as_str()) => sources.push(path),
+                _ => {}
+            }
+        }
+    }
+
+    let sources: Vec<(String, String)> = sources
+        .par_iter()
+        .filter(|p| fs::metadata(p).is_ok_and(|m| m.len() <= MAX_SOURCE_SIZE))
+        .filter_map(|p| Some((p.to_string_lossy().to_string(), fs::read_to_string(p).ok()?)))
+        .collect();
+
+    let mut assets: Vec<Asset> = found
+        .into_par_iter()
+        .filter_map(|(path, relative, kind)| {
+            let size = fs::metadata(&path).ok()?.len();
+            let needle = asset_path(&relative)?;
+            let (width, height) = dimensions(&path, kind).unzip();
+            Some(Asset {
+                path: path.to_string_lossy().to_string(),
+                kind: kind.to_string(),
+                format: extension(&path),
+                size,
+                hash: file_io::file_hash(&path).ok()?,
+                width,
+                height,
+                references: sources
+                    .iter()
+                    .filter(|(_, source)| mentions(source, needle))
+                    .map(|(file, _)| file.clone())
+                    .collect(),
+                relative,
+            })
+        })
+        .collect();
+
+    assets.sort_by(|a, b| a.relative.cmp(&b.relative));
+    assets
+}
@@ -0,0 +1,287 @@
+//! Size optimisation for web assets.
+//!
+//! PNGs are recompressed losslessly with oxipng. PNGs and JPEGs can also be
+//! converted to WebP and AVIF, written next to the original. Conversions
+//! start from decoded pixels, so they never carry metadata over, and never
+//! overwrite an existing file.
+
+use crate::errors::{AppError, AppResult};
+use crate::file_io;
+use image::codecs::avif::AvifEncoder;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageReader};
+use oxipng::StripChunks;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// oxipng preset, 2 is its default balance of time and size
+const PNG_PRESET: u8 = 2;
+
+/// ravif speed from 1 (slowest, smallest) to 10
+const AVIF_SPEED: u8 = 6;
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct OptimizeOptions {
+    pub recompress_png: bool, // lossless, replaces the PNG when smaller
+    pub webp: bool,           // write a `.webp` next to each image
+    pub avif: bool,           // write an `.avif` next to each image
+    pub quality: u8,          // WebP and AVIF quality, 1 to 100
+    pub strip_metadata: bool, // drop text, EXIF and other chunks that do not change the pixels
+    pub dry_run: bool,        // compute the sizes but write nothing
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            recompress_png: true,
+            webp: false,
+            avif: false,
+            quality: 80,
+            strip_metadata: true,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OptimizedFile {
+    path: String,
+    output: String,
+    format: String, // png, webp or avif
+    before: u64,
+    after: u64,
+    written: bool,
+    note: Option<String>, // why nothing was written, or the error
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct OptimizeReport {
+    files: Vec<OptimizedFile>,
+    before: u64, // the sources, each counted once
+    after: u64,  // the smallest version of each source
+    dry_run: bool,
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Decode with the EXIF orientation applied, which converted files would lose otherwise
+fn decode(path: &Path) -> AppResult<DynamicImage> {
+    let error = |e: image::ImageError| {
+        AppError::NotSupported(format!("Cannot decode {}: {}", path.display(), e))
+    };
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(error)?;
+    let orientation = decoder.orientation().map_err(error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(error)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn recompress_png(bytes: &[u8], options: &OptimizeOptions) -> AppResult<Vec<u8>> {
+    let mut png = oxipng::Options::from_preset(PNG_PRESET);
+    png.strip = if options.strip_metadata {
+        StripChunks::Safe
+    } else {
+        StripChunks::None
+    };
+    oxipng::optimize_from_memory(bytes, &png).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+fn encode_webp(image: &DynamicImage, quality: u8) -> Vec<u8> {
+    let (width, height) = (image.width(), image.height());
+    let encoded = if image.color().has_alpha() {
+        webp::Encoder::from_rgba(image.to_rgba8().as_raw(), width, height).encode(quality as f32)
+    } else {
+        webp::Encoder::from_rgb(image.to_rgb8().as_raw(), width, height).encode(quality as f32)
+    };
+    encoded.to_vec()
+}
+
+fn encode_avif(image: &DynamicImage, quality: u8) -> AppResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let rgba = image.to_rgba8();
+    AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, quality)
+        .write_image(
+            rgba.as_raw(),
+            rgba.width(),
+            rgba.height(),
+            image::ExtendedColorType::Rgba8,
+        )
+        .map_err(|e| AppError::Internal(format!("AVIF encoding failed: {}", e)))?;
+    Ok(bytes)
+}
+
+/// Report one output of `source` and write it unless this is a dry run.
+///
+/// `replace` outputs overwrite the source, those are only written when smaller.
+fn finish(
+    source: &Path,
+    output: PathBuf,
+    before: u64,
+    encoded: Result<Vec<u8>, String>,
+    replace: bool,
+    dry_run: bool,
+) -> OptimizedFile {
+    let mut file = OptimizedFile {
+        path: source.to_string_lossy().to_string(),
+        format: extension(&output),
+        output: output.to_string_lossy().to_string(),
+        before,
+        after: before,
+        written: false,
+        note: None,
+    };
+    match encoded {
+        Ok(bytes) if bytes.len() as u64 >= before && replace => {
+            file.note = Some("Already optimal".to_string());
+        }
+        Ok(bytes) if bytes.len() as u64 >= before => {
+            file.after = bytes.len() as u64;
+            file.note = Some("Larger than the original, not written".to_string());
+        }
+        Ok(bytes) => {
+            file.after = bytes.len() as u64;
+            if !dry_run {
+                match file_io::atomic_write(&output, &bytes) {
+                    Ok(()) => file.written = true,
+                    Err(e) => file.note = Some(e.to_string()),
+                }
+            }
+        }
+        Err(e) => file.note = Some(e),
+    }
+    file
+}
+
+/// Why a conversion must not write `output`, if it must not
+fn conflict(output: &Path, shared: &HashSet<PathBuf>) -> Option<String> {
+    if shared.contains(output) {
+        Some("Another image converts to the same file, not written".to_string())
+    } else if fs::symlink_metadata(output).is_ok() {
+        Some("The file exists already, not overwritten".to_string())
+    } else {
+        None
+    }
+}
+
+/// Every output for one image, in the order png, webp, avif.
+///
+/// Conversions to a path in `shared` or to an existing file are skipped.
+fn optimize_file(
+    path: &Path,
+    options: &OptimizeOptions,
+    shared: &HashSet<PathBuf>,
+) -> Vec<OptimizedFile> {
+    let dry_run = options.dry_run;
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return vec![finish(
+                path,
+                path.to_path_buf(),
+                0,
+                Err(e.to_string()),
+                true,
+                dry_run,
+            )]
+        }
+    };
+    let before = bytes.len() as u64;
+    let mut files = Vec::new();
+
+    if options.recompress_png && extension(path) == "png" {
+        let png = recompress_png(&bytes, options).map_err(|e| e.to_string());
+        files.push(finish(path, path.to_path_buf(), before, png, true, dry_run));
+    }
+
+    let quality = options.quality.clamp(1, 100);
+    let mut image = None;
+    for format in conversions(options) {
+        let output = path.with_extension(format);
+        let encoded = match conflict(&output, shared) {
+            Some(note) => Err(note),
+            None => match image.get_or_insert_with(|| decode(path).map_err(|e| e.to_string())) {
+                Ok(image) if format == "webp" => Ok(encode_webp(image, quality)),
+                Ok(image) => encode_avif(image, quality).map_err(|e| e.to_string()),
+                Err(e) => Err(e.clone()),
+            },
+        };
+        files.push(finish(path, output, before, encoded, false, dry_run));
+    }
+    files
+}
+
+/// Extensions of the formats images are converted to
+fn conversions(options: &OptimizeOptions) -> impl Iterator<Item = &'static str> {
+    [(options.webp, "webp"), (options.avif, "avif")]
+        .into_iter()
+        .filter_map(|(enabled, format)| enabled.then_some(format))
+}
+
+/// Optimize `paths` in parallel, calling `progress` with the number of
+/// images done and the total after each one
+pub fn optimize(
+    paths: &[PathBuf],
+    options: &OptimizeOptions,
+    progress: impl Fn(usize, usize) + Sync,
+) -> OptimizeReport {
+    let done = AtomicUsize::new(0);
+    // Only PNGs and JPEGs: the other formats are either lossless already or not for the web
+    let paths: Vec<&PathBuf> = paths
+        .iter()
+        .filter(|p| matches!(extension(p).as_str(), "png" | "jpg" | "jpeg"))
+        .collect();
+
+    // `logo.png` and `logo.jpg` would both write `logo.webp`
+    let mut targets: HashMap<PathBuf, usize> = HashMap::new();
+    for path in &paths {
+        for format in conversions(options) {
+            *targets.entry(path.with_extension(format)).or_default() += 1;
+        }
+    }
+    let shared: HashSet<PathBuf> = targets
+        .into_iter()
+        .filter(|(_, sources)| *sources > 1)
+        .map(|(target, _)| target)
+        .collect();
+
+    let files: Vec<OptimizedFile> = paths
+        .par_iter()
+        .flat_map_iter(|path| {
+            let files = optimize_file(path, options, &shared);
+            progress(done.fetch_add(1, Ordering::Relaxed) + 1, paths.len());
+            files
+        })
+        .collect();
+
+    // Each source counts once, with the smallest output that is kept
+    let mut sizes: HashMap<&str, (u64, u64)> = HashMap::new();
+    for file in &files {
+        let size = sizes
+            .entry(&file.path)
+            .or_insert((file.before, file.before));
+        if file.written || (options.dry_run && file.note.is_none()) {
+            size.1 = size.1.min(file.after);
+        }
+    }
+    let (before, after) = sizes.values().fold((0, 0), |(before, after), size| {
+        (before + size.0, after + size.1)
+    });
+
+    OptimizeReport {
+        before,
+        after,
+        files,
+        dry_run: options.dry_run,
+    }
+}
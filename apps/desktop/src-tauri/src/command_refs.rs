@@ -0,0 +1,429 @@
+use crate::commands::search::{read_text, walk_workspace};
+use crate::symbols::Symbol;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Location {
+    file: String,
+    line: usize,   // 1-based
+    column: usize, // UTF-16
+}
+
+/// A `#[tauri::command]` with its registration status and frontend callers
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandRef {
+    name: String,
+    path: String, // module path within its crate, `commands::fs::read_file`
+    location: Location,
+    registered: bool, // listed in a `generate_handler!`
+    call_sites: Vec<Location>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Diagnostic {
+    severity: String, // error, warning or info
+    code: String,
+    message: String,
+    location: Location,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CommandReport {
+    commands: Vec<CommandRef>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn location(file: &Path, source: &str, offset: usize) -> Location {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    Location {
+        file: file.to_string_lossy().to_string(),
+        line: source[..offset].matches('\n').count() + 1,
+        column: source[line_start..offset].encode_utf16().count(),
+    }
+}
+
+fn diagnostic(severity: &str, code: &str, message: String, location: Location) -> Diagnostic {
+    Diagnostic {
+        severity: severity.to_string(),
+        code: code.to_string(),
+        message,
+        location,
+    }
+}
+
+/// Crate source folder and module path of a Rust file, so
+/// `src/commands/fs.rs` is `commands::fs` of the crate in `src`
+fn module_of(file: &Path) -> (PathBuf, Vec<String>) {
+    let parts: Vec<_> = file.components().collect();
+    let Some(src) = parts.iter().rposition(|c| c.as_os_str() == "src") else {
+        return (file.parent().unwrap_or(file).to_path_buf(), Vec::new());
+    };
+    let mut module: Vec<String> = parts[src + 1..]
+        .iter()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if let Some(last) = module.pop() {
+        let stem = last.strip_suffix(".rs").unwrap_or(&last);
+        let crate_root = module.is_empty() && (stem == "lib" || stem == "main");
+        if stem != "mod" && !crate_root {
+            module.push(stem.to_string());
+        }
+    }
+    (parts[..=src].iter().collect(), module)
+}
+
+/// The identifiers and punctuation of Rust source, without comments and literals
+fn tokens(source: &str) -> Vec<&str> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &source[i..];
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("/*") {
+            // Block comments nest in Rust
+            let mut depth = 0;
+            while i < bytes.len() {
+                if source[i..].starts_with("/*") {
+                    depth += 1;
+                    i += 2;
+                } else if source[i..].starts_with("*/") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        } else if let Some(len) = raw_string_len(rest) {
+            i += len;
+        } else if c == b'"' || rest.starts_with("b\"") {
+            i += usize::from(c == b'b') + 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i += 1;
+        } else if c == b'\'' {
+            // `'a'` and `'\n'` are char literals, `'a` alone is a lifetime
+            let body = &rest[1..];
+            let len = if body.starts_with('\\') {
+                body[2.min(body.len())..].find('\'').map_or(1, |j| j + 4)
+            } else {
+                let ch = body.chars().next().map_or(0, char::len_utf8);
+                if body[ch..].starts_with('\'') {
+                    ch + 2
+                } else {
+                    1
+                }
+            };
+            i += len;
+        } else if c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80 {
+            let len = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(&rest[..len]);
+            i += len;
+        } else {
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            tokens.push(&rest[..len]);
+            i += len;
+        }
+    }
+    tokens
+}
+
+/// Length of the raw string literal (`r"..."`, `br#"..."#`) at the start of `text`
+fn raw_string_len(text: &str) -> Option<usize> {
+    let after_prefix = text.strip_prefix("br").or_else(|| text.strip_prefix('r'))?;
+    let hashes = after_prefix.len() - after_prefix.trim_start_matches('#').len();
+    let body = after_prefix[hashes..].strip_prefix('"')?;
+    let closing = format!("\"{}", "#".repeat(hashes));
+    let end = body.find(&closing)?;
+    Some(text.len() - body.len() + end + closing.len())
+}
+
+/// Paths listed in the `generate_handler!` calls of a Rust file.
+///
+/// Attributes such as `#[cfg(...)]` on an entry are skipped. A path is
+/// returned as written, `crate::`, `self::` and `super::` included.
+fn registered_commands(source: &str) -> Vec<Vec<String>> {
+    let tokens = tokens(source);
+    let mut paths = Vec::new();
+    let mut i = 0;
+    while i + 2 < tokens.len() {
+        let is_handler = tokens[i] == "generate_handler"
+            && tokens[i + 1] == "!"
+            && matches!(tokens[i + 2], "[" | "(" | "{");
+        if !is_handler {
+            i += 1;
+            continue;
+        }
+
+        i += 3;
+        let mut depth = 1;
+        let mut path = Vec::new();
+        while i < tokens.len() && depth > 0 {
+            match tokens[i] {
+                "[" | "(" | "{" => depth += 1,
+                "]" | ")" | "}" => depth -= 1,
+                "," if depth == 1 && !path.is_empty() => paths.push(std::mem::take(&mut path)),
+                ":" | "#" | "!" | "," => {}
+                ident if depth == 1 => path.push(ident.to_string()),
+                _ => {}
+            }
+            i += 1;
+        }
+        if !path.is_empty() {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Where a `generate_handler!` entry points, relative to the module listing it
+enum Registration {
+    Path(Vec<String>), // full module path and name within the crate
+    Imported(String),  // a bare name brought in by `use`, its module is unknown
+}
+
+fn resolve_registration(module: &[String], mut path: Vec<String>) -> Registration {
+    let mut full = module.to_vec();
+    match path.first().map(String::as_str) {
+        Some("crate") => {
+            full.clear();
+            path.remove(0);
+        }
+        Some("self") => {
+            path.remove(0);
+        }
+        Some("super") => {
+            while path.first().map(String::as_str) == Some("super") {
+                full.pop();
+                path.remove(0);
+            }
+        }
+        _ if path.len() == 1 => return Registration::Imported(path.remove(0)),
+        _ => {}
+    }
+    full.extend(path);
+    Registration::Path(full)
+}
+
+/// `invoke("name")` calls with a literal command name, plugin commands excluded
+fn invoke_calls(source: &str) -> Vec<(String, usize)> {
+    static INVOKE: OnceLock<Regex> = OnceLock::new();
+    let invoke = INVOKE.get_or_init(|| {
+        Regex::new(r#"\binvoke\s*(?:<[^>()]*>)?\(\s*(?:"([^"]+)"|'([^']+)'|`([^`$]+)`)"#).unwrap()
+    });
+
+    invoke
+        .captures_iter(source)
+        .filter_map(|caps| {
+            let name = caps.get(1).or(caps.get(2)).or(caps.get(3))?;
+            (!name.as_str().starts_with("plugin:"))
+                .then(|| (name.as_str().to_string(), name.start()))
+        })
+        .collect()
+}
+
+/// Cross-reference the `#[tauri::command]`s of a workspace with `generate_handler!`
+/// and the frontend `invoke` calls.
+///
+/// Reports commands that are never registered, invokes that no registered
+/// command answers, and registered commands that nothing invokes.
+pub fn analyze(commands: Vec<Symbol>, roots: &[PathBuf], skip: &[String]) -> CommandReport {
+    let mut registered: HashSet<(PathBuf, Vec<String>)> = HashSet::new();
+    let mut imported: HashSet<(PathBuf, String)> = HashSet::new();
+    let mut calls: HashMap<String, Vec<Location>> = HashMap::new();
+
+    for root in roots {
+        for entry in walk_workspace(root, skip, false).filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            let is_rust = ext == "rs";
+            if !is_rust && !matches!(ext, "ts" | "tsx" | "js" | "jsx" | "mjs" | "svelte") {
+                continue;
+            }
+            let Some(source) = read_text(path) else {
+                continue;
+            };
+
+            if is_rust {
+                let (crate_dir, module) = module_of(path);
+                for entry in registered_commands(&source) {
+                    match resolve_registration(&module, entry) {
+                        Registration::Path(full) => {
+                            registered.insert((crate_dir.clone(), full));
+                        }
+                        Registration::Imported(name) => {
+                            imported.insert((crate_dir.clone(), name));
+                        }
+                    }
+                }
+            } else {
+                for (name, offset) in invoke_calls(&source) {
+                    calls
+                        .entry(name)
+                        .or_default()
+                        .push(location(path, &source, offset));
+                }
+            }
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut defined: HashMap<String, bool> = HashMap::new();
+    let mut refs: Vec<CommandRef> = commands
+        .into_iter()
+        .map(|symbol| {
+            let (crate_dir, mut module) = module_of(Path::new(&symbol.file));
+            module.extend(symbol.container.clone());
+            module.push(symbol.name.clone());
+            // Commands are registered by path, same-named ones in other modules do not count
+            let is_registered = registered.contains(&(crate_dir.clone(), module.clone()))
+                || imported.contains(&(crate_dir, symbol.name.clone()));
+            *defined.entry(symbol.name.clone()).or_default() |= is_registered;
+            CommandRef {
+                path: module.join("::"),
+                registered: is_registered,
+                call_sites: calls.get(&symbol.name).cloned().unwrap_or_default(),
+                location: Location {
+                    file: symbol.file,
+                    line: symbol.line,
+                    column: symbol.column,
+                },
+                name: symbol.name,
+            }
+        })
+        .collect();
+    refs.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+
+    for command in &refs {
+        if !command.registered {
+            diagnostics.push(diagnostic(
+                "warning",
+                "unregistered-command",
+                format!(
+                    "`{}` is a #[tauri::command] but is not listed in generate_handler!",
+                    command.path
+                ),
+                command.location.clone(),
+            ));
+        } else if command.call_sites.is_empty() {
+            diagnostics.push(diagnostic(
+                "info",
+                "unused-command",
+                format!(
+                    "`{}` is registered but never invoked from the frontend",
+                    command.name
+                ),
+                command.location.clone(),
+            ));
+        }
+    }
+
+    let mut invoked: Vec<_> = calls.into_iter().collect();
+    invoked.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, sites) in invoked {
+        let message = match defined.get(&name) {
+            Some(true) => continue,
+            Some(false) => format!(
+                "`{}` exists but is not registered, this invoke will fail",
+                name
+            ),
+            None => format!("No #[tauri::command] named `{}`", name),
+        };
+        for site in sites {
+            diagnostics.push(diagnostic(
+                "error",
+                "unknown-command",
+                message.clone(),
+                site,
+            ));
+        }
+    }
+
+    CommandReport {
+        commands: refs,
+        diagnostics,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_handler_entry() {
+        let source = r#"
+            // generate_handler![commented::out]
+            let s = "generate_handler![in_a_string]";
+            let c = ']';
+            builder.invoke_handler(tauri::generate_handler![
+                commands::fs::read_file, // reads
+                #[cfg(target_os = "macos")]
+                commands::mac::open_xcode,
+                /* commands::old::gone, */
+                crate::other::ping,
+                local
+            ]);
+        "#;
+        let paths: Vec<String> = registered_commands(source)
+            .iter()
+            .map(|p| p.join("::"))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "commands::fs::read_file",
+                "commands::mac::open_xcode",
+                "crate::other::ping",
+                "local"
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_entries_against_the_listing_module() {
+        let module = vec!["app".to_string()];
+        let path = |p: &str| p.split("::").map(String::from).collect::<Vec<_>>();
+        let full = |r: Registration| match r {
+            Registration::Path(p) => p.join("::"),
+            Registration::Imported(name) => format!("use {}", name),
+        };
+
+        assert_eq!(
+            full(resolve_registration(&module, path("fs::read"))),
+            "app::fs::read"
+        );
+        assert_eq!(
+            full(resolve_registration(&module, path("crate::fs::read"))),
+            "fs::read"
+        );
+        assert_eq!(
+            full(resolve_registration(&module, path("super::read"))),
+            "read"
+        );
+        assert_eq!(
+            full(resolve_registration(&module, path("read"))),
+            "use read"
+        );
+
+        let (crate_dir, module) = module_of(Path::new("/w/app/src/commands/mod.rs"));
+        assert_eq!(
+            (crate_dir, module),
+            (PathBuf::from("/w/app/src"), vec!["commands".to_string()])
+        );
+        assert!(module_of(Path::new("/w/app/src/lib.rs")).1.is_empty());
+    }
+}
@@ -1,22 +1,47 @@
-use crate::models::FileNode;
+use crate::asset_inventory::{self, Asset};
+use crate::asset_optimizer::{self, OptimizeOptions, OptimizeReport};
+use crate::commands::search::workspace_exclude;
 use crate::errors::{AppError, AppResult};
+use crate::icon_audit::{self, IconReport};
+use crate::icon_mask::{self, MaskOptions, PlatformMasks};
+use crate::icons::{
+    self, AndroidOptions, Background, IconOptions, IconSource, IosOptions, OutputDirs,
+};
+use crate::sandbox::WorkspaceRoots;
+use crate::splash;
+use crate::thumbnails::{Thumbnail, ThumbnailCache};
+use crate::workspace::WorkspaceManager;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::Rgba;
 use std::path::{Path, PathBuf};
-use image::DynamicImage;
-use std::fs::File;
-use std::io::BufWriter;
+use tauri::{AppHandle, Emitter, State};
 use walkdir::WalkDir;
 
+/// Inventory of the images, fonts, audio, video and SVGs under `static/`,
+/// `public/`, `src/assets` and `src-tauri/icons`, with the files using them
 #[tauri::command]
-pub async fn get_assets() -> AppResult<Vec<FileNode>> {
-    // Phase 4 will implement asset scanning
-    Ok(vec![])
+pub async fn get_assets(
+    roots: State<'_, WorkspaceRoots>,
+    manager: State<'_, WorkspaceManager>,
+    project_root: String,
+) -> AppResult<Vec<Asset>> {
+    let root = roots.resolve(&project_root)?;
+    let skip = workspace_exclude(&manager, &root);
+    Ok(asset_inventory::scan(&[root], &skip))
 }
 
 /// Find the icons directory in a Tauri project
 #[tauri::command]
-pub async fn find_icons_dir(project_root: String) -> AppResult<Option<String>> {
-    let root = Path::new(&project_root);
-    
+pub async fn find_icons_dir(
+    roots: State<'_, WorkspaceRoots>,
+    project_root: String,
+) -> AppResult<Option<String>> {
+    let root = &roots.resolve(&project_root)?;
+    Ok(locate_icons_dir(root).map(|dir| dir.to_string_lossy().to_string()))
+}
+
+fn locate_icons_dir(root: &Path) -> Option<PathBuf> {
     // Common path patterns for Tauri icons directory (using proper path joining)
     let path_patterns: Vec<PathBuf> = vec![
         root.join("src-tauri").join("icons"),
@@ -24,20 +49,30 @@ pub async fn find_icons_dir(project_root: String) -> AppResult<Option<String>> {
         root.join("tauri").join("icons"),
         root.join("icons"),
         // Monorepo patterns
-        root.join("apps").join("desktop").join("src-tauri").join("icons"),
-        root.join("packages").join("desktop").join("src-tauri").join("icons"),
+        root.join("apps")
+            .join("desktop")
+            .join("src-tauri")
+            .join("icons"),
+        root.join("packages")
+            .join("desktop")
+            .join("src-tauri")
+            .join("icons"),
         root.join("app").join("src-tauri").join("icons"),
     ];
-    
+
     // First try direct paths
     for path in &path_patterns {
         if path.exists() && path.is_dir() {
-            return Ok(Some(path.to_string_lossy().to_string()));
+            return Some(path.to_path_buf());
         }
     }
-    
+
     // If not found, search recursively (max depth 5 for monorepos)
-    for entry in WalkDir::new(root).max_depth(5).into_iter().filter_map(|e| e.ok()) {
+    for entry in WalkDir::new(root)
+        .max_depth(5)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
         let path = entry.path();
         if path.is_dir() {
             let name = path.file_name().unwrap_or_default().to_string_lossy();
@@ -46,242 +81,233 @@ pub async fn find_icons_dir(project_root: String) -> AppResult<Option<String>> {
                 if let Some(parent) = path.parent() {
                     let parent_name = parent.file_name().unwrap_or_default().to_string_lossy();
                     if parent_name == "src-tauri" || parent_name == "tauri" {
-                        return Ok(Some(path.to_string_lossy().to_string()));
+                        return Some(path.to_path_buf());
                     }
                 }
             }
         }
     }
-    
-    Ok(None)
+
+    None
 }
 
-use std::process::Command;
+fn open_source(roots: &WorkspaceRoots, path: &str) -> AppResult<IconSource> {
+    let resolved = roots.resolve(path)?;
+    if !resolved.exists() {
+        return Err(AppError::NotFound(format!(
+            "Source image not found: {}",
+            path
+        )));
+    }
+    IconSource::open(&resolved)
+}
 
+/// Generate the icon sets for the selected `platforms` (desktop, ios,
+/// android) from one source image.
+///
+/// Desktop PNGs, `icon.ico`, `icon.icns` and the Windows Store logos go into
+/// `target_dir`, iOS icons into `gen/apple/Assets.xcassets` and Android mipmaps into
+/// `gen/android` next to it. `masks` cut each platform's icons to the shape
+/// of its preset (the ICNS, the ICO and Store logos or the legacy launcher
+/// icons), platforms without a mask stay square. iOS masks the AppIcon itself.
+///
+/// iOS icons are flattened onto `ios_background` (white by default) and go
+/// into an `AppIcon.appiconset`. `ios_single_size` writes one 1024px icon
+/// for Xcode 14 and later, which can also get a `ios_dark` image and a
+/// tinted variant (`ios_tinted`).
+///
+/// Android also gets adaptive icons. `android_foreground` is an image for
+/// the foreground layer (the source by default) and `android_background`
+/// either a `#rrggbb` color or an image for the background layer.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn generate_icons(
+    roots: State<'_, WorkspaceRoots>,
     source_path: String,
     target_dir: String,
     platforms: Vec<String>,
-    apply_squircle: bool,
+    masks: Option<Vec<MaskOptions>>,
+    android_foreground: Option<String>,
+    android_background: Option<String>,
+    ios_background: Option<String>,
+    ios_single_size: Option<bool>,
+    ios_dark: Option<String>,
+    ios_tinted: Option<bool>,
 ) -> AppResult<String> {
-    let source = Path::new(&source_path);
-    let target = Path::new(&target_dir);
-
-    if !source.exists() {
-        return Err(AppError::Internal("Source file does not exist".into()));
-    }
-
-    // Load image for custom processing
-    let img = image::open(source)
-        .map_err(|e| AppError::Internal(format!("Failed to open image: {}", e)))?;
-
-    // Create target directory if needed
-    if !target.exists() {
-        std::fs::create_dir_all(target)
-            .map_err(|e| AppError::Internal(format!("Failed to create target directory: {}", e)))?;
-    }
-
-    // Step 1: Run Tauri CLI for standard desktop PNGs
-    let mut cmd = Command::new("pnpm");
-    cmd.args(["tauri", "icon", &source_path, "--output", &target_dir]);
-    
-    let output = cmd.output().or_else(|_| {
-        Command::new("npx")
-            .args(["tauri", "icon", &source_path, "--output", &target_dir])
-            .output()
-    }).map_err(|e| AppError::Internal(format!("Failed to execute tauri icon CLI: {}", e)))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(AppError::Internal(format!("Tauri CLI failed: {}", stderr)));
-    }
+    let dirs = OutputDirs::resolve(&roots, Path::new(&target_dir))?;
+    let source = open_source(&roots, &source_path)?;
 
-    // Step 2: Post-process for "Native" look on macOS/Windows if requested
-    if platforms.contains(&"desktop".to_string()) {
-        if apply_squircle {
-            generate_native_icns(&img, target)?;
-            generate_native_ico(&img, target)?;
-        }
-    }
-
-    // Step 3: Move mobile icons to proper structure if requested
-    if platforms.contains(&"ios".to_string()) {
-        move_ios_icons(target)?;
-    }
-    if platforms.contains(&"android".to_string()) {
-        move_android_icons(target)?;
-    }
-
-    Ok("Icons generated successfully".into())
-}
-
-/// Move iOS icons from flat structure to gen/apple/
-fn move_ios_icons(base_dir: &Path) -> AppResult<()> {
-    // Standard path: sibling to 'icons' or in base_dir
-    let parent = if base_dir.file_name().map_or(false, |n| n == "icons") {
-        base_dir.parent().unwrap_or(base_dir)
+    let android = if platforms.iter().any(|p| p == "android") {
+        let foreground = match android_foreground.as_deref().filter(|p| !p.is_empty()) {
+            Some(path) => Some(open_source(&roots, path)?),
+            None => None,
+        };
+        let background = match android_background.as_deref().filter(|b| !b.is_empty()) {
+            Some(color) if color.starts_with('#') => {
+                Some(Background::Color(icons::parse_color(color)?))
+            }
+            Some(path) => Some(Background::Image(open_source(&roots, path)?)),
+            None => None,
+        };
+        Some(AndroidOptions {
+            foreground,
+            background,
+        })
     } else {
-        base_dir
+        None
     };
-    
-    let apple_dir = parent.join("gen").join("apple");
-    std::fs::create_dir_all(&apple_dir)
-        .map_err(|e| AppError::Internal(format!("Failed to create gen/apple in {:?}: {}", parent, e)))?;
 
-    // Move all AppIcon-*.png files
-    for entry in std::fs::read_dir(base_dir).map_err(|e| AppError::Internal(e.to_string()))? {
-        let entry = entry.map_err(|e| AppError::Internal(e.to_string()))?;
-        let name = entry.file_name().to_string_lossy().to_string();
-        if name.starts_with("AppIcon-") && name.ends_with(".png") {
-            let dest = apple_dir.join(&name);
-            std::fs::rename(entry.path(), dest)
-                .map_err(|e| AppError::Internal(format!("Failed to move {}: {}", name, e)))?;
-        }
-    }
-    Ok(())
-}
-
-/// Move Android icons from flat structure to gen/android/mipmap-*/
-fn move_android_icons(base_dir: &Path) -> AppResult<()> {
-    // Standard path: sibling to 'icons' or in base_dir
-    let parent = if base_dir.file_name().map_or(false, |n| n == "icons") {
-        base_dir.parent().unwrap_or(base_dir)
+    let ios = if platforms.iter().any(|p| p == "ios") {
+        let background = match ios_background.as_deref().filter(|c| !c.is_empty()) {
+            Some(color) => icons::parse_color(color)?,
+            None => Rgba([255, 255, 255, 255]),
+        };
+        let dark = match ios_dark.as_deref().filter(|p| !p.is_empty()) {
+            Some(path) => Some(open_source(&roots, path)?),
+            None => None,
+        };
+        Some(IosOptions {
+            background,
+            single_size: ios_single_size.unwrap_or(false),
+            dark,
+            tinted: ios_tinted.unwrap_or(false),
+        })
     } else {
-        base_dir
+        None
     };
-    
-    let android_dir = parent.join("gen").join("android");
-    
-    // Android mipmap densities
-    let densities = ["mdpi", "hdpi", "xhdpi", "xxhdpi", "xxxhdpi"];
-    for density in densities {
-        let mipmap_dir = android_dir.join(format!("mipmap-{}", density));
-        std::fs::create_dir_all(&mipmap_dir)
-            .map_err(|e| AppError::Internal(format!("Failed to create mipmap-{}: {}", density, e)))?;
-    }
-
-    // Move mipmap-*/*.png files
-    for entry in std::fs::read_dir(base_dir).map_err(|e| AppError::Internal(e.to_string()))? {
-        let entry = entry.map_err(|e| AppError::Internal(e.to_string()))?;
-        let name = entry.file_name().to_string_lossy().to_string();
-        if name.starts_with("mipmap-") && entry.path().is_dir() {
-            // This is a mipmap folder from tauri icon, move its contents
-            let src_mipmap = entry.path();
-            let dest_mipmap = android_dir.join(&name);
-            std::fs::create_dir_all(&dest_mipmap)
-                .map_err(|e| AppError::Internal(format!("Failed to create {}: {}", name, e)))?;
-            
-            for file in std::fs::read_dir(&src_mipmap).map_err(|e| AppError::Internal(e.to_string()))? {
-                let file = file.map_err(|e| AppError::Internal(e.to_string()))?;
-                let file_name = file.file_name();
-                let dest = dest_mipmap.join(&file_name);
-                std::fs::rename(file.path(), dest)
-                    .map_err(|e| AppError::Internal(format!("Failed to move {:?}: {}", file_name, e)))?;
-            }
-            // Remove empty source mipmap folder
-            let _ = std::fs::remove_dir(&src_mipmap);
-        }
-    }
-    Ok(())
-}
 
+    let options = IconOptions {
+        desktop: platforms.iter().any(|p| p == "desktop"),
+        ios,
+        android,
+        masks: PlatformMasks::new(&masks.unwrap_or_default())?,
+    };
+    let written = icons::generate(&source, &dirs, &options)?;
 
-fn apply_mac_mask(img: &DynamicImage, size: u32) -> DynamicImage {
-    // macOS Design Guidelines:
-    // Content area is approx 80-82% of the icon size
-    let content_size = (size as f32 * 0.82) as u32;
-    let padding = (size - content_size) / 2;
-    
-    // Resize content
-    let resized_content = img.resize_exact(content_size, content_size, image::imageops::FilterType::Lanczos3);
-    
-    // Create transparent canvas
-    let mut canvas = image::ImageBuffer::new(size, size);
-    
-    // Apply squircle mask (approx with rounded rect)
-    // Radius for macOS is ~22.3% of the size
-    let radius = (content_size as f32 * 0.223) as f32;
-    
-    let content_rgba = resized_content.to_rgba8();
-    
-    for y in 0..content_size {
-        for x in 0..content_size {
-            let px = content_rgba.get_pixel(x, y);
-            
-            // Simple rounded rect mask check
-            let dx = if x < radius as u32 { radius - x as f32 }
-                     else if x > content_size - radius as u32 { x as f32 - (content_size as f32 - radius) }
-                     else { 0.0 };
-            let dy = if y < radius as u32 { radius - y as f32 }
-                     else if y > content_size - radius as u32 { y as f32 - (content_size as f32 - radius) }
-                     else { 0.0 };
-            
-            let mut alpha = px[3];
-            if dx > 0.0 && dy > 0.0 {
-                let dist = (dx*dx + dy*dy).sqrt();
-                if dist > radius {
-                    alpha = 0;
-                } else if dist > radius - 1.0 {
-                    // Anti-aliasing
-                    let aa = (radius - dist).clamp(0.0, 1.0);
-                    alpha = (alpha as f32 * aa) as u8;
-                }
-            }
-            
-            canvas.put_pixel(x + padding, y + padding, image::Rgba([px[0], px[1], px[2], alpha]));
-        }
-    }
-    
-    DynamicImage::ImageRgba8(canvas)
+    log::info!(
+        "Generated {} icon files from {}",
+        written.len(),
+        source_path
+    );
+    Ok(format!("Generated {} icon files", written.len()))
 }
 
-fn generate_native_icns(img: &DynamicImage, output_dir: &Path) -> AppResult<()> {
-    let mut family = icns::IconFamily::new();
-    let sizes = vec![16, 32, 64, 128, 256, 512, 1024];
-
-    for &size in &sizes {
-        let masked = apply_mac_mask(img, size);
-        let rgba = masked.to_rgba8();
-        let image = icns::Image::from_data(icns::PixelFormat::RGBA, size, size, rgba.into_raw())
-            .map_err(|e| AppError::Internal(e.to_string()))?;
-        family.add_icon(&image).map_err(|e| AppError::Internal(e.to_string()))?;
-    }
+/// Generate the Android 12 splash screen and the iOS launch screen: `logo_path`
+/// centred on `background` (`#rrggbb`).
+///
+/// Resources go into `gen/android` and `gen/apple` next to the icons folder
+/// `target_dir`.
+#[tauri::command]
+pub async fn generate_splash(
+    roots: State<'_, WorkspaceRoots>,
+    logo_path: String,
+    background: String,
+    target_dir: String,
+) -> AppResult<String> {
+    let dirs = OutputDirs::resolve(&roots, Path::new(&target_dir))?;
+    let logo = open_source(&roots, &logo_path)?;
+    let written = splash::generate(&logo, icons::parse_color(&background)?, &dirs)?;
 
-    let file = File::create(output_dir.join("icon.icns"))
-        .map_err(|e| AppError::Internal(format!("Failed to create ICNS: {}", e)))?;
-    let mut writer = BufWriter::new(file);
-    family.write(&mut writer).map_err(|e| AppError::Internal(e.to_string()))?;
+    log::info!(
+        "Generated {} splash files from {}",
+        written.len(),
+        logo_path
+    );
+    Ok(format!("Generated {} splash files", written.len()))
+}
 
-    Ok(())
+/// Render `source_path` cut to `mask` at `size` pixels (256 by default) and
+/// return it as a `data:image/png;base64,` URL, so the mask can be tuned
+/// before any file is written
+#[tauri::command]
+pub async fn preview_icon_mask(
+    roots: State<'_, WorkspaceRoots>,
+    source_path: String,
+    mask: MaskOptions,
+    size: Option<u32>,
+) -> AppResult<String> {
+    let source = open_source(&roots, &source_path)?;
+    let size = size.unwrap_or(256).clamp(16, 1024);
+    let png = icons::encode_png(&icon_mask::apply(&source, size, &mask.mask()))?;
+    Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
 }
 
-fn generate_native_ico(img: &DynamicImage, output_dir: &Path) -> AppResult<()> {
-    let sizes = vec![16, 24, 32, 48, 64, 128, 256];
-    let mut icon_dir = ico::IconDir::new(ico::ResourceType::Icon);
+/// Check the icons of the project at `project_root`: the `bundle.icon` list
+/// in `tauri.conf.json`, the icons folder and the `gen/android` and
+/// `gen/apple` resources.
+///
+/// Reports missing files and resolutions, wrong sizes, alpha channels in App
+/// Store icons, non-square sources and icons older than `source_path`
+/// (`app-icon.png` or `app-icon.svg` by default).
+#[tauri::command]
+pub async fn validate_icons(
+    roots: State<'_, WorkspaceRoots>,
+    project_root: String,
+    source_path: Option<String>,
+) -> AppResult<IconReport> {
+    let root = roots.resolve(&project_root)?;
+    let source = match source_path.as_deref().filter(|p| !p.is_empty()) {
+        Some(path) => Some(roots.resolve(path)?),
+        None => None,
+    };
+    let icons_dir = locate_icons_dir(&root);
+    Ok(icon_audit::audit(
+        &root,
+        icons_dir.as_deref(),
+        source.as_deref(),
+    ))
+}
 
-    for &size in &sizes {
-        // Windows icons usually don't have the squircle mask but padding is good
-        let content_size = (size as f32 * 0.9) as u32; // Less padding for Windows
-        let padding = (size - content_size) / 2;
-        let resized = img.resize_exact(content_size, content_size, image::imageops::FilterType::Lanczos3);
-        
-        let mut canvas = image::ImageBuffer::new(size, size);
-        let resized_rgba = resized.to_rgba8();
-        for y in 0..content_size {
-            for x in 0..content_size {
-                canvas.put_pixel(x + padding, y + padding, *resized_rgba.get_pixel(x, y));
-            }
+/// Recompress the PNGs and convert the PNGs and JPEGs in the asset folders
+/// of `project_root` (or just `paths`) to WebP and AVIF.
+///
+/// Runs in parallel and emits `asset-optimize-progress` events
+/// (`{ done, total }`). With `dry_run` only the sizes are reported.
+#[tauri::command]
+pub async fn optimize_assets(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+    manager: State<'_, WorkspaceManager>,
+    project_root: String,
+    paths: Option<Vec<String>>,
+    options: Option<OptimizeOptions>,
+) -> AppResult<OptimizeReport> {
+    let root = roots.resolve(&project_root)?;
+    let files = match paths {
+        Some(paths) => paths
+            .iter()
+            .map(|p| roots.resolve(p))
+            .collect::<AppResult<Vec<_>>>()?,
+        None => {
+            let skip = workspace_exclude(&manager, &root);
+            asset_inventory::images(&[root], &skip)
         }
-        
-        let image = ico::IconImage::from_rgba_data(size, size, canvas.into_raw());
-        icon_dir.add_entry(ico::IconDirEntry::encode(&image).map_err(|e| AppError::Internal(e.to_string()))?);
-    }
+    };
 
-    let file = File::create(output_dir.join("icon.ico"))
-        .map_err(|e| AppError::Internal(format!("Failed to create ICO: {}", e)))?;
-    let mut writer = BufWriter::new(file);
-    icon_dir.write(&mut writer).map_err(|e| AppError::Internal(e.to_string()))?;
+    let options = options.unwrap_or_default();
+    let report = asset_optimizer::optimize(&files, &options, |done, total| {
+        let _ = app.emit(
+            "asset-optimize-progress",
+            serde_json::json!({ "done": done, "total": total }),
+        );
+    });
+    Ok(report)
+}
 
-    Ok(())
+/// Downscaled preview of the image at `path`, at most `size` pixels (128 by
+/// default) on the longer edge, with its dimensions, color type, bit depth,
+/// frame count and EXIF orientation.
+///
+/// Previews are cached under the app cache dir until the file changes. ICO
+/// and ICNS files also list every embedded size, each with its own PNG.
+#[tauri::command]
+pub async fn get_thumbnail(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+    cache: State<'_, ThumbnailCache>,
+    path: String,
+    size: Option<u32>,
+) -> AppResult<Thumbnail> {
+    let resolved = roots.resolve(&path)?;
+    cache.get(&app, &resolved, size.unwrap_or(128))
 }
@@ -0,0 +1,248 @@
+use crate::errors::{AppError, AppResult};
+use crate::sandbox::WorkspaceRoots;
+use std::fs;
+use std::path::Path;
+use tauri::State;
+use walkdir::WalkDir;
+
+/// Whether anything lives at `path`, a dangling symlink included
+fn entry_exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// Fail if `path` already exists and overwriting was not requested
+fn ensure_can_write(path: &Path, overwrite: bool) -> AppResult<()> {
+    if entry_exists(path) && !overwrite {
+        return Err(AppError::AlreadyExists(path.to_string_lossy().to_string()));
+    }
+    Ok(())
+}
+
+/// Fail if `path` is one of the workspace roots, which only closing the workspace may remove
+fn ensure_not_root(roots: &WorkspaceRoots, path: &Path) -> AppResult<()> {
+    if roots.is_root(path) {
+        return Err(AppError::PermissionDenied(format!(
+            "Cannot modify a workspace root: {}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Fail if `path` itself is a symlink, whose target may live outside the workspace
+fn ensure_not_symlink(path: &Path) -> AppResult<()> {
+    if fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Err(AppError::NotSupported(format!(
+            "Cannot copy a symlink: {}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `path` is a symlink or a directory containing one
+fn contains_symlink(path: &Path) -> bool {
+    WalkDir::new(path)
+        .follow_root_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .any(|e| e.path_is_symlink())
+}
+
+/// Recursively copy a file or directory.
+///
+/// Symlinks are skipped: copying them would duplicate whatever they point
+/// at, which may live outside the workspace.
+fn copy_recursive(source: &Path, destination: &Path) -> AppResult<()> {
+    for entry in WalkDir::new(source)
+        .follow_root_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if entry.path_is_symlink() {
+            log::warn!("Skipping symlink while copying: {}", entry.path().display());
+            continue;
+        }
+
+        let relative = entry
+            .path()
+            .strip_prefix(source)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let target = if relative.as_os_str().is_empty() {
+            destination.to_path_buf()
+        } else {
+            destination.join(relative)
+        };
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `from` can be renamed to `to`, which only works within one file system
+#[cfg(unix)]
+fn same_device(from: &Path, to: &Path) -> AppResult<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let parent = to.parent().unwrap_or(to);
+    Ok(fs::symlink_metadata(from)?.dev() == fs::metadata(parent)?.dev())
+}
+
+#[cfg(not(unix))]
+fn same_device(from: &Path, to: &Path) -> AppResult<bool> {
+    Ok(from.components().next() == to.components().next())
+}
+
+/// Move a file or directory to the OS trash
+fn move_to_trash(path: &Path) -> AppResult<()> {
+    trash::delete(path)
+        .map_err(|e| AppError::Io(format!("Failed to move {} to trash: {}", path.display(), e)))
+}
+
+/// Trash whatever currently lives at `path` so it can be replaced. Only call
+/// this once every check has passed, right before the path is written.
+fn remove_existing(path: &Path) -> AppResult<()> {
+    if entry_exists(path) {
+        move_to_trash(path)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn create_file(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<()> {
+    let target = roots.resolve_entry(&path)?;
+    ensure_can_write(&target, false)?;
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // `create_new` refuses anything already there and never follows a symlink
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(target)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn create_dir(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<()> {
+    let target = roots.resolve_entry(&path)?;
+    ensure_can_write(&target, false)?;
+
+    fs::create_dir_all(target)?;
+    Ok(())
+}
+
+/// Move a file or directory to the OS trash
+#[tauri::command]
+pub async fn delete_path(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<()> {
+    let target = roots.resolve_entry(&path)?;
+    if !entry_exists(&target) {
+        return Err(AppError::NotFound(format!("Path not found: {}", path)));
+    }
+    ensure_not_root(&roots, &target)?;
+
+    move_to_trash(&target)
+}
+
+#[tauri::command]
+pub async fn rename_path(
+    roots: State<'_, WorkspaceRoots>,
+    old_path: String,
+    new_path: String,
+) -> AppResult<()> {
+    let source = roots.resolve_entry(&old_path)?;
+    let destination = roots.resolve_entry(&new_path)?;
+
+    if !entry_exists(&source) {
+        return Err(AppError::NotFound(format!("Path not found: {}", old_path)));
+    }
+    ensure_not_root(&roots, &source)?;
+
+    // A case-only rename on a case-insensitive file system resolves to the same file
+    let same_file =
+        entry_exists(&destination) && fs::canonicalize(&destination).ok() == Some(source.clone());
+    if !same_file {
+        ensure_can_write(&destination, false)?;
+    }
+
+    fs::rename(source, destination)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn copy_path(
+    roots: State<'_, WorkspaceRoots>,
+    source: String,
+    destination: String,
+    overwrite: Option<bool>,
+) -> AppResult<()> {
+    let from = roots.resolve_entry(&source)?;
+    let to = roots.resolve_entry(&destination)?;
+
+    if !entry_exists(&from) {
+        return Err(AppError::NotFound(format!("Path not found: {}", source)));
+    }
+    if to.starts_with(&from) {
+        return Err(AppError::NotSupported(format!(
+            "Cannot copy {} into itself",
+            source
+        )));
+    }
+    ensure_not_symlink(&from)?;
+    ensure_not_root(&roots, &to)?;
+    ensure_can_write(&to, overwrite.unwrap_or(false))?;
+
+    remove_existing(&to)?;
+    copy_recursive(&from, &to)
+}
+
+#[tauri::command]
+pub async fn move_path(
+    roots: State<'_, WorkspaceRoots>,
+    source: String,
+    destination: String,
+    overwrite: Option<bool>,
+) -> AppResult<()> {
+    let from = roots.resolve_entry(&source)?;
+    let to = roots.resolve_entry(&destination)?;
+
+    if !entry_exists(&from) {
+        return Err(AppError::NotFound(format!("Path not found: {}", source)));
+    }
+    if to.starts_with(&from) {
+        return Err(AppError::NotSupported(format!(
+            "Cannot move {} into itself",
+            source
+        )));
+    }
+    ensure_not_root(&roots, &from)?;
+    ensure_not_root(&roots, &to)?;
+    ensure_can_write(&to, overwrite.unwrap_or(false))?;
+
+    // rename fails across devices, those fall back to copy + delete. The copy
+    // skips symlinks, so deleting the source afterwards would lose them.
+    let cross_device = !same_device(&from, &to)?;
+    if cross_device && contains_symlink(&from) {
+        return Err(AppError::NotSupported(format!(
+            "Cannot move {} across devices, it contains symlinks",
+            source
+        )));
+    }
+
+    remove_existing(&to)?;
+    if cross_device {
+        copy_recursive(&from, &to)?;
+        if from.is_dir() {
+            fs::remove_dir_all(&from)?;
+        } else {
+            fs::remove_file(&from)?;
+        }
+    } else {
+        fs::rename(&from, &to)?;
+    }
+    Ok(())
+}
@@ -1,6 +1,9 @@
 use crate::errors::AppResult;
+use crate::sandbox::WorkspaceRoots;
 use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use tauri::State;
 
 #[derive(Serialize, Clone, Debug)]
 pub struct FileStatus {
@@ -15,8 +18,17 @@ pub struct GitStatusResult {
     pub unstaged: Vec<FileStatus>,
 }
 
+/// Resolve a file of the repository at `repo`, which must stay inside the workspace
+fn repo_file(roots: &WorkspaceRoots, repo: &Path, file: &str) -> AppResult<PathBuf> {
+    roots.resolve(&repo.join(file).to_string_lossy())
+}
+
 #[tauri::command]
-pub async fn git_status(path: String) -> AppResult<GitStatusResult> {
+pub async fn git_status(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+) -> AppResult<GitStatusResult> {
+    let path = roots.resolve(&path)?;
     // Run git status --porcelain
     let output = Command::new("git")
         .args(&["status", "--porcelain"])
@@ -59,25 +71,43 @@ pub async fn git_status(path: String) -> AppResult<GitStatusResult> {
 }
 
 #[tauri::command]
-pub async fn git_stage(path: String, file: String) -> AppResult<()> {
+pub async fn git_stage(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+    file: String,
+) -> AppResult<()> {
+    let path = roots.resolve(&path)?;
     Command::new("git")
-        .args(&["add", &file])
+        .arg("add")
+        .arg("--")
+        .arg(repo_file(&roots, &path, &file)?)
         .current_dir(path)
         .output()?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn git_unstage(path: String, file: String) -> AppResult<()> {
+pub async fn git_unstage(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+    file: String,
+) -> AppResult<()> {
+    let path = roots.resolve(&path)?;
     Command::new("git")
-        .args(&["reset", "HEAD", &file])
+        .args(["reset", "HEAD", "--"])
+        .arg(repo_file(&roots, &path, &file)?)
         .current_dir(path)
         .output()?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn git_commit(path: String, message: String) -> AppResult<()> {
+pub async fn git_commit(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+    message: String,
+) -> AppResult<()> {
+    let path = roots.resolve(&path)?;
     Command::new("git")
         .args(&["commit", "-m", &message])
         .current_dir(path)
@@ -86,7 +116,8 @@ pub async fn git_commit(path: String, message: String) -> AppResult<()> {
 }
 
 #[tauri::command]
-pub async fn git_diff_staged(path: String) -> AppResult<String> {
+pub async fn git_diff_staged(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<String> {
+    let path = roots.resolve(&path)?;
     let output = Command::new("git")
         .args(&["diff", "--staged"])
         .current_dir(path)
@@ -96,7 +127,8 @@ pub async fn git_diff_staged(path: String) -> AppResult<String> {
 }
 
 #[tauri::command]
-pub async fn git_push(path: String) -> AppResult<()> {
+pub async fn git_push(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<()> {
+    let path = roots.resolve(&path)?;
     Command::new("git")
         .args(&["push"])
         .current_dir(path)
@@ -105,7 +137,8 @@ pub async fn git_push(path: String) -> AppResult<()> {
 }
 
 #[tauri::command]
-pub async fn git_pull(path: String) -> AppResult<()> {
+pub async fn git_pull(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<()> {
+    let path = roots.resolve(&path)?;
     Command::new("git")
         .args(&["pull"])
         .current_dir(path)
@@ -114,16 +147,23 @@ pub async fn git_pull(path: String) -> AppResult<()> {
 }
 
 #[tauri::command]
-pub async fn git_discard_changes(path: String, file: String) -> AppResult<()> {
+pub async fn git_discard_changes(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+    file: String,
+) -> AppResult<()> {
+    let path = roots.resolve(&path)?;
     Command::new("git")
-        .args(&["restore", &file])
+        .args(["restore", "--"])
+        .arg(repo_file(&roots, &path, &file)?)
         .current_dir(path)
         .output()?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn git_branch(path: String) -> AppResult<String> {
+pub async fn git_branch(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<String> {
+    let path = roots.resolve(&path)?;
     let output = Command::new("git")
         .args(&["rev-parse", "--abbrev-ref", "HEAD"])
         .current_dir(path)
@@ -1,9 +1,13 @@
 pub mod assets;
 pub mod config;
 pub mod env_check;
+pub mod file_ops;
 pub mod git;
 pub mod mobile;
 pub mod project_init;
 pub mod quick_commands;
+pub mod replace;
 pub mod runner;
 pub mod search;
+pub mod symbols;
+pub mod workspace;
@@ -1,16 +1,22 @@
-use crate::models::FileNode;
-use crate::errors::AppResult;
+use crate::archive;
+use crate::models::{FileContent, FileNode, FileVersion};
+use crate::errors::{AppError, AppResult};
+use crate::file_index::FileIndex;
+use crate::file_io;
+use crate::sandbox::WorkspaceRoots;
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
+use tauri::State;
 
 
 #[tauri::command]
-pub async fn scan_project(path: String) -> AppResult<FileNode> {
-    let root_path = Path::new(&path);
+pub async fn scan_project(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<FileNode> {
+    let root_path = roots.resolve(&path)?;
     if !root_path.exists() {
         return Err(crate::errors::AppError::NotFound(format!("Path not found: {}", path)));
     }
 
-    Ok(build_tree(root_path)?)
+    Ok(build_tree(&root_path)?)
 }
 
 fn build_tree(path: &Path) -> std::io::Result<FileNode> {
@@ -51,45 +57,151 @@ fn build_tree(path: &Path) -> std::io::Result<FileNode> {
 
 
 #[tauri::command]
-pub async fn read_file(path: String) -> AppResult<String> {
-    let content = std::fs::read_to_string(path)?;
-    Ok(content)
-}
+pub async fn read_file(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<String> {
+    let bytes = match archive::split(&path) {
+        Some((outer, entry)) => archive::read_entry(&roots.resolve(outer)?, entry)?,
+        None => std::fs::read(roots.resolve(&path)?)?,
+    };
+    if file_io::is_binary(&bytes) {
+        return Err(AppError::NotSupported(format!("Binary file: {}", path)));
+    }
 
-#[tauri::command]
-pub async fn write_file(path: String, content: String) -> AppResult<()> {
-    std::fs::write(path, content)?;
-    Ok(())
+    let (encoding, bom_len) = file_io::detect_encoding(&bytes);
+    Ok(file_io::decode(&bytes[bom_len..], encoding, true))
 }
 
-#[tauri::command]
-pub async fn create_file(path: String) -> AppResult<()> {
-    std::fs::File::create(path)?;
-    Ok(())
-}
+/// Files larger than this are only read partially unless `max_bytes` says otherwise
+const DEFAULT_MAX_READ: u64 = 10 * 1024 * 1024;
 
+/// Read a file with encoding detection, binary classification and an optional byte range.
+///
+/// Binary files come back with empty `content`. When the range stops before
+/// the end of the file `truncated` is set and no `version` is computed.
+/// Archive entries (`app.apk!/AndroidManifest.xml`) are read-only and never
+/// carry a `version`.
 #[tauri::command]
-pub async fn create_dir(path: String) -> AppResult<()> {
-    std::fs::create_dir_all(path)?;
-    Ok(())
-}
+pub async fn read_file_ex(
+    roots: State<'_, WorkspaceRoots>,
+    index: State<'_, FileIndex>,
+    path: String,
+    offset: Option<u64>,
+    max_bytes: Option<u64>,
+) -> AppResult<FileContent> {
+    if let Some((outer, entry)) = archive::split(&path) {
+        let bytes = archive::read_entry(&roots.resolve(outer)?, entry)?;
+        let size = bytes.len() as u64;
+        return read_range(Cursor::new(bytes), size, offset, max_bytes, |_| None);
+    }
 
-#[tauri::command]
-pub async fn delete_path(path: String) -> AppResult<()> {
+    let path = roots.resolve(&path)?;
     let metadata = std::fs::metadata(&path)?;
-    if metadata.is_dir() {
-        std::fs::remove_dir_all(path)?;
-    } else {
-        std::fs::remove_file(path)?;
+    if offset.is_none() {
+        index.record_opened(&path);
     }
-    Ok(())
+    let file = std::fs::File::open(&path)?;
+    read_range(file, metadata.len(), offset, max_bytes, |bytes| {
+        Some(file_io::version_of(&metadata, bytes))
+    })
 }
 
-#[tauri::command]
-pub async fn rename_path(old_path: String, new_path: String) -> AppResult<()> {
-    std::fs::rename(old_path, new_path)?;
-    Ok(())
+/// The body of `read_file_ex`, `version` is called with the whole content
+/// when it was read in full
+fn read_range<R: Read + Seek>(
+    mut file: R,
+    size: u64,
+    offset: Option<u64>,
+    max_bytes: Option<u64>,
+    version: impl FnOnce(&[u8]) -> Option<FileVersion>,
+) -> AppResult<FileContent> {
+    // Encoding, BOM and binary detection always look at the start of the file
+    let mut head = Vec::new();
+    (&mut file).take(8192).read_to_end(&mut head)?;
+    let is_binary = file_io::is_binary(&head);
+    let (encoding, bom_len) = file_io::detect_encoding(&head);
+
+    let mut offset = offset.unwrap_or(0).max(bom_len as u64).min(size);
+    let is_utf16 = encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE;
+    if is_utf16 && (offset - bom_len as u64) % 2 == 1 {
+        offset += 1;
+    }
+    let max_bytes = max_bytes.unwrap_or(DEFAULT_MAX_READ);
+
+    let mut chunk = Vec::new();
+    if !is_binary {
+        file.seek(SeekFrom::Start(offset))?;
+        (&mut file).take(max_bytes).read_to_end(&mut chunk)?;
+    }
+    let truncated = !is_binary && offset + (chunk.len() as u64) < size;
+    let whole_file = !is_binary && offset == bom_len as u64 && !truncated;
+
+    // Re-check the encoding on the full chunk: a UTF-8 head can hide Latin-1 further down
+    let encoding = if whole_file && bom_len == 0 {
+        file_io::detect_encoding(&chunk).0
+    } else {
+        encoding
+    };
+    let content = file_io::decode(&chunk, encoding, !truncated);
+
+    let version = if whole_file {
+        let mut bytes = head[..bom_len].to_vec();
+        bytes.extend_from_slice(&chunk);
+        version(&bytes)
+    } else {
+        None
+    };
+
+    Ok(FileContent {
+        line_ending: if file_io::uses_crlf(&content) { "crlf" } else { "lf" }.to_string(),
+        content,
+        encoding: encoding.name().to_string(),
+        has_bom: bom_len > 0,
+        is_binary,
+        size,
+        offset,
+        bytes_read: chunk.len() as u64,
+        truncated,
+        version,
+    })
 }
 
+/// Save a file atomically, keeping its encoding, line endings, BOM and permissions.
+///
+/// When `expected_mtime` or `expected_hash` is given and the file changed on
+/// disk since then, the write is refused with `AppError::Conflict`. Passing
+/// `encoding` converts the file to that encoding instead.
+#[tauri::command]
+pub async fn write_file(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+    content: String,
+    expected_mtime: Option<u64>,
+    expected_hash: Option<String>,
+    encoding: Option<String>,
+) -> AppResult<FileVersion> {
+    if archive::split(&path).is_some() {
+        return Err(AppError::NotSupported(format!("Archive entries are read-only: {}", path)));
+    }
+    let path = roots.resolve(&path)?;
+
+    let existing = if path.exists() {
+        let existing = std::fs::read(&path)?;
+        file_io::check_unchanged(&path, &existing, expected_mtime, expected_hash.as_deref())?;
+        existing
+    } else if expected_mtime.is_some() || expected_hash.is_some() {
+        // The editor loaded this file, saving now would quietly bring it back
+        return Err(AppError::Conflict(format!("{} was deleted on disk", path.display())));
+    } else {
+        Vec::new()
+    };
+
+    let bytes = file_io::match_file_format(&existing, &content, encoding.as_deref())?;
+    file_io::atomic_write(&path, &bytes)?;
 
+    file_io::file_version(&path)
+}
 
+#[tauri::command]
+pub async fn stat_file(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<FileVersion> {
+    let path = roots.resolve(&path)?;
+    file_io::file_version(&path)
+}
@@ -1,7 +1,9 @@
 use crate::errors::AppResult;
+use crate::sandbox::WorkspaceRoots;
 use std::fs;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
+use tauri::State;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectInfo {
@@ -15,8 +17,12 @@ pub struct ProjectInfo {
 
 /// Detect the package manager used in the project
 #[tauri::command]
-pub async fn detect_package_manager(path: String) -> AppResult<String> {
-    let mut current_path = Path::new(&path).to_path_buf();
+pub async fn detect_package_manager(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+) -> AppResult<String> {
+    let start = roots.resolve(&path)?;
+    let mut current_path = start.clone();
     
     // Walk up the directory tree to find lock files (for monorepo support)
     loop {
@@ -48,7 +54,7 @@ pub async fn detect_package_manager(path: String) -> AppResult<String> {
     }
     
     // Fallback to npm if package.json exists in original path
-    if Path::new(&path).join("package.json").exists() {
+    if start.join("package.json").exists() {
         return Ok("npm".to_string());
     }
     
@@ -57,8 +63,11 @@ pub async fn detect_package_manager(path: String) -> AppResult<String> {
 
 /// Detect project type and capabilities
 #[tauri::command]
-pub async fn detect_project_info(path: String) -> AppResult<ProjectInfo> {
-    let project_path = Path::new(&path);
+pub async fn detect_project_info(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+) -> AppResult<ProjectInfo> {
+    let project_path = &roots.resolve(&path)?;
     
     // Detect package manager - walk up to find lock files
     let package_manager = {
@@ -137,8 +146,11 @@ pub struct AndroidVersion {
 
 /// Read Android version from build.gradle.kts
 #[tauri::command]
-pub async fn read_android_version(path: String) -> AppResult<AndroidVersion> {
-    let gradle_path = Path::new(&path).join("src-tauri/gen/android/app/build.gradle.kts");
+pub async fn read_android_version(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+) -> AppResult<AndroidVersion> {
+    let gradle_path = roots.resolve(&path)?.join("src-tauri/gen/android/app/build.gradle.kts");
     
     if !gradle_path.exists() {
         return Err(crate::errors::AppError::NotFound("Android build.gradle.kts not found".to_string()));
@@ -167,8 +179,11 @@ pub async fn read_android_version(path: String) -> AppResult<AndroidVersion> {
 
 /// Update Android version (increment versionCode and versionName patch)
 #[tauri::command]
-pub async fn update_android_version(path: String) -> AppResult<AndroidVersion> {
-    let gradle_path = Path::new(&path).join("src-tauri/gen/android/app/build.gradle.kts");
+pub async fn update_android_version(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+) -> AppResult<AndroidVersion> {
+    let gradle_path = roots.resolve(&path)?.join("src-tauri/gen/android/app/build.gradle.kts");
     
     if !gradle_path.exists() {
         return Err(crate::errors::AppError::NotFound("Android build.gradle.kts not found".to_string()));
@@ -229,9 +244,12 @@ pub struct ArtifactInfo {
 
 /// Find Android artifacts (.apk and .aab) in search path
 #[tauri::command]
-pub async fn find_android_artifacts(path: String) -> AppResult<Vec<ArtifactInfo>> {
+pub async fn find_android_artifacts(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+) -> AppResult<Vec<ArtifactInfo>> {
     let mut artifacts = Vec::new();
-    let search_root = Path::new(&path).join("src-tauri/gen/android/app/build/outputs");
+    let search_root = roots.resolve(&path)?.join("src-tauri/gen/android/app/build/outputs");
     
     if !search_root.exists() {
         return Ok(artifacts);
@@ -272,21 +290,26 @@ pub async fn find_android_artifacts(path: String) -> AppResult<Vec<ArtifactInfo>
 /// Copy and rename build artifact to builds folder
 #[tauri::command]
 pub async fn manage_build_artifact(
+    roots: State<'_, WorkspaceRoots>,
     project_root: String,
     source_path: String,
     target_name: String
 ) -> AppResult<String> {
-    let builds_dir = Path::new(&project_root).join("builds");
+    let builds_dir = roots.resolve(&project_root)?.join("builds");
     if !builds_dir.exists() {
         fs::create_dir_all(&builds_dir)?;
     }
 
-    let source = Path::new(&source_path);
+    let source = roots.resolve(&source_path)?;
     if !source.exists() {
         return Err(crate::errors::AppError::NotFound(format!("Source artifact not found: {}", source_path)));
     }
 
-    let target_path = builds_dir.join(target_name);
+    // target_name comes from the UI, make sure it cannot climb out of builds/
+    let target_path = roots.resolve(&builds_dir.join(&target_name).to_string_lossy())?;
+    if target_path.parent() != Some(builds_dir.as_path()) {
+        return Err(crate::errors::AppError::PermissionDenied(format!("Invalid artifact name: {}", target_name)));
+    }
     fs::copy(source, &target_path)?;
 
     Ok(target_path.to_string_lossy().to_string())
@@ -294,10 +317,12 @@ pub async fn manage_build_artifact(
 
 /// Open Xcode project (macOS only)
 #[tauri::command]
-pub async fn open_xcode_project(_path: String) -> AppResult<()> {
+pub async fn open_xcode_project(roots: State<'_, WorkspaceRoots>, path: String) -> AppResult<()> {
+    let xcode_path = roots.resolve(&path)?.join("src-tauri/gen/apple");
     #[cfg(target_os = "macos")]
     {
-        let xcode_path = Path::new(&_path).join("src-tauri/gen/apple");
+        // The project folder may be a symlink leading out of the workspace
+        let xcode_path = roots.resolve(&xcode_path.to_string_lossy())?;
         if !xcode_path.exists() {
             return Err(crate::errors::AppError::NotFound("iOS project not found".to_string()));
         }
@@ -323,6 +348,7 @@ pub async fn open_xcode_project(_path: String) -> AppResult<()> {
     
     #[cfg(not(target_os = "macos"))]
     {
+        let _ = xcode_path;
         Err(crate::errors::AppError::NotSupported("Opening Xcode is only supported on macOS".to_string()))
     }
 }
@@ -0,0 +1,470 @@
+use crate::commands::search::{
+    build_matcher, candidate_files, match_segments, read_text, workspace_exclude, SearchOptions,
+};
+use crate::errors::{AppError, AppResult};
+use crate::file_io;
+use crate::sandbox::WorkspaceRoots;
+use crate::workspace::WorkspaceManager;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+/// Stop collecting previews after this many matches
+const MAX_PREVIEW_MATCHES: usize = 5000;
+
+/// Undo backups kept in `replace-undo`, older ones are deleted
+const MAX_UNDO_ENTRIES: usize = 20;
+
+#[derive(Serialize, Clone)]
+pub struct ReplaceMatch {
+    id: usize, // index of the match within the file, used to select it
+    line: usize,
+    end_line: usize,
+    before: String, // the lines covered by the match
+    after: String,  // the same lines with only this match replaced
+}
+
+#[derive(Serialize, Clone)]
+pub struct FileReplacePreview {
+    file: String,
+    hash: String, // content hash before the replace, or after it once applied
+    matches: Vec<ReplaceMatch>,
+    diff: String, // unified-style hunks with every listed match applied
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ReplaceSelection {
+    file: String,
+    hash: String,
+    match_ids: Vec<usize>,
+}
+
+#[derive(Serialize)]
+pub struct ReplaceResult {
+    files: Vec<FileReplacePreview>,
+    undo_id: Option<String>, // set once the replacement was applied
+}
+
+#[derive(Serialize, Deserialize)]
+struct UndoManifest {
+    id: String,
+    files: Vec<UndoEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UndoEntry {
+    file: String,
+    backup: String,        // file name inside the undo folder
+    replaced_hash: String, // hash right after the replace, to detect later edits
+}
+
+/// One replacement to make in a file, in byte offsets of the original content
+struct PlannedEdit {
+    start: usize,
+    end: usize,
+    replacement: String,
+    line: usize,
+    end_line: usize,
+    last_line: usize, // last line to preview, past `end_line` when the match ends with a line break
+}
+
+/// Find every match in `content` and compute its replacement text.
+///
+/// In regex mode `$1`/`${name}` in the replacement expand to capture groups,
+/// otherwise the replacement is inserted literally.
+fn plan_edits(
+    content: &str,
+    matcher: &Regex,
+    options: &SearchOptions,
+    replacement: &str,
+) -> Vec<PlannedEdit> {
+    let mut line_starts = vec![0];
+    line_starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+    let line_of = |offset: usize| line_starts.partition_point(|&s| s <= offset);
+
+    let mut edits = Vec::new();
+    for (base, segment) in match_segments(content, options.multiline) {
+        for caps in matcher.captures_iter(segment) {
+            let whole = caps.get(0).unwrap();
+            let mut text = String::new();
+            if options.regex {
+                caps.expand(replacement, &mut text);
+            } else {
+                text.push_str(replacement);
+            }
+
+            let start = base + whole.start();
+            let end = base + whole.end();
+            edits.push(PlannedEdit {
+                start,
+                end,
+                replacement: text,
+                line: line_of(start),
+                end_line: line_of(end.saturating_sub(1).max(start)),
+                last_line: line_of(end),
+            });
+        }
+    }
+    edits
+}
+
+/// Apply edits (sorted by offset, non-overlapping) to a slice of `content` starting at `base`
+fn apply_edits(content: &str, base: usize, edits: &[&PlannedEdit]) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut pos = 0;
+    for edit in edits {
+        out.push_str(&content[pos..edit.start - base]);
+        out.push_str(&edit.replacement);
+        pos = edit.end - base;
+    }
+    out.push_str(&content[pos..]);
+    out
+}
+
+/// Byte range of lines `first..=last` (1-based), without the final line break
+fn lines_range(content: &str, first: usize, last: usize) -> (usize, usize) {
+    let mut starts = vec![0];
+    starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+    let start = starts[first - 1];
+    let end = starts
+        .get(last)
+        .map(|&next| next - 1)
+        .unwrap_or(content.len());
+    (start, end)
+}
+
+/// Build the preview of the given edits for one file
+fn preview_file(
+    file: &str,
+    content: &str,
+    hash: String,
+    edits: &[(usize, &PlannedEdit)],
+) -> FileReplacePreview {
+    let matches = edits
+        .iter()
+        .map(|(id, edit)| {
+            let (start, end) = lines_range(content, edit.line, edit.last_line);
+            let before = &content[start..end];
+            ReplaceMatch {
+                id: *id,
+                line: edit.line,
+                end_line: edit.end_line,
+                before: before.to_string(),
+                after: apply_edits(before, start, &[*edit]),
+            }
+        })
+        .collect();
+
+    // Group edits whose lines touch into hunks
+    let mut diff = String::new();
+    let mut i = 0;
+    while i < edits.len() {
+        let first = edits[i].1.line;
+        let mut last = edits[i].1.last_line;
+        let mut j = i + 1;
+        while j < edits.len() && edits[j].1.line <= last {
+            last = last.max(edits[j].1.last_line);
+            j += 1;
+        }
+
+        let (start, end) = lines_range(content, first, last);
+        let old = &content[start..end];
+        let group: Vec<&PlannedEdit> = edits[i..j].iter().map(|(_, e)| *e).collect();
+        let new = apply_edits(old, start, &group);
+
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            first,
+            old.lines().count().max(1),
+            first,
+            new.lines().count().max(1)
+        ));
+        for line in old.split('\n') {
+            diff.push_str(&format!("-{}\n", line.trim_end_matches('\r')));
+        }
+        for line in new.split('\n') {
+            diff.push_str(&format!("+{}\n", line.trim_end_matches('\r')));
+        }
+        i = j;
+    }
+
+    FileReplacePreview {
+        file: file.to_string(),
+        hash,
+        matches,
+        diff,
+    }
+}
+
+fn undo_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .join("replace-undo");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Delete all but the newest `MAX_UNDO_ENTRIES` backups
+fn prune_undo(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut backups: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    backups.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in backups.into_iter().skip(MAX_UNDO_ENTRIES) {
+        if let Err(e) = fs::remove_dir_all(&path) {
+            log::warn!("Failed to delete undo backup {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Write every file or none: on failure the files already written are restored
+fn write_all_or_rollback(writes: &[(PathBuf, Vec<u8>, Vec<u8>)]) -> AppResult<()> {
+    for (done, (path, _, new)) in writes.iter().enumerate() {
+        if let Err(e) = file_io::atomic_write(path, new) {
+            for (path, original, _) in &writes[..done] {
+                if let Err(restore_err) = file_io::atomic_write(path, original) {
+                    log::error!("Failed to roll back {}: {:?}", path.display(), restore_err);
+                }
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Replace matches of a search across the project.
+///
+/// Without `selection` nothing is written: every match is returned with a
+/// per-file diff. With `selection` only the chosen match ids are replaced,
+/// all files are written atomically, and an `undo_id` is returned for
+/// `undo_replace`. Files that changed since the preview are refused with
+/// `AppError::Conflict`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn replace_in_files(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+    manager: State<'_, WorkspaceManager>,
+    query: String,
+    path: String,
+    include: Option<String>,
+    exclude: Option<String>,
+    options: Option<SearchOptions>,
+    replacement: String,
+    selection: Option<Vec<ReplaceSelection>>,
+) -> AppResult<ReplaceResult> {
+    let root = roots.resolve(&path)?;
+    let options = options.unwrap_or_default();
+    let matcher = build_matcher(&query, &options)?;
+
+    let Some(selection) = selection else {
+        let mut files = Vec::new();
+        let mut total = 0;
+        let skip = workspace_exclude(&manager, &root);
+        for file_path in candidate_files(&root, &include, &exclude, &skip, options.include_ignored)
+        {
+            let Some(content) = read_text(&file_path) else {
+                continue;
+            };
+            let edits = plan_edits(&content, &matcher, &options, &replacement);
+            if edits.is_empty() {
+                continue;
+            }
+
+            total += edits.len();
+            let indexed: Vec<_> = edits.iter().enumerate().collect();
+            files.push(preview_file(
+                &file_path.to_string_lossy(),
+                &content,
+                file_io::content_hash(content.as_bytes()),
+                &indexed,
+            ));
+            if total >= MAX_PREVIEW_MATCHES {
+                break;
+            }
+        }
+        return Ok(ReplaceResult {
+            files,
+            undo_id: None,
+        });
+    };
+
+    // Plan everything before touching the disk
+    let mut writes = Vec::new();
+    let mut previews = Vec::new();
+    for selected in &selection {
+        let file_path = roots.resolve(&selected.file)?;
+        if !file_path.starts_with(&root) {
+            return Err(AppError::PermissionDenied(format!(
+                "{} is outside the searched folder",
+                selected.file
+            )));
+        }
+
+        let content = fs::read_to_string(&file_path)?;
+        if file_io::content_hash(content.as_bytes()) != selected.hash {
+            return Err(AppError::Conflict(format!(
+                "{} changed since the preview",
+                selected.file
+            )));
+        }
+
+        let edits = plan_edits(&content, &matcher, &options, &replacement);
+        let chosen: Vec<(usize, &PlannedEdit)> = edits
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| selected.match_ids.contains(id))
+            .collect();
+        if chosen.is_empty() {
+            continue;
+        }
+
+        let refs: Vec<&PlannedEdit> = chosen.iter().map(|(_, e)| *e).collect();
+        let replaced = apply_edits(&content, 0, &refs);
+        previews.push(preview_file(
+            &selected.file,
+            &content,
+            file_io::content_hash(replaced.as_bytes()),
+            &chosen,
+        ));
+        writes.push((file_path, content.into_bytes(), replaced.into_bytes()));
+    }
+
+    if writes.is_empty() {
+        return Ok(ReplaceResult {
+            files: previews,
+            undo_id: None,
+        });
+    }
+
+    // Keep the originals so the replace can be rolled back later
+    let undo_id = uuid::Uuid::new_v4().to_string();
+    let dir = undo_dir(&app)?.join(&undo_id);
+    fs::create_dir_all(&dir)?;
+    let mut manifest = UndoManifest {
+        id: undo_id.clone(),
+        files: Vec::new(),
+    };
+    for (n, (path, original, replaced)) in writes.iter().enumerate() {
+        let backup = format!("{}.orig", n);
+        fs::write(dir.join(&backup), original)?;
+        manifest.files.push(UndoEntry {
+            file: path.to_string_lossy().to_string(),
+            backup,
+            replaced_hash: file_io::content_hash(replaced),
+        });
+    }
+    let manifest_json =
+        serde_json::to_vec_pretty(&manifest).map_err(|e| AppError::Internal(e.to_string()))?;
+    fs::write(dir.join("manifest.json"), manifest_json)?;
+
+    if let Err(e) = write_all_or_rollback(&writes) {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(e);
+    }
+    prune_undo(&undo_dir(&app)?);
+
+    log::info!(
+        "Replaced matches in {} files (undo {})",
+        writes.len(),
+        undo_id
+    );
+    Ok(ReplaceResult {
+        files: previews,
+        undo_id: Some(undo_id),
+    })
+}
+
+/// Restore the files changed by a `replace_in_files` call.
+///
+/// Fails with `AppError::Conflict` if any of them was edited after the replace.
+#[tauri::command]
+pub async fn undo_replace(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+    undo_id: String,
+) -> AppResult<Vec<String>> {
+    if undo_id.is_empty()
+        || !undo_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(AppError::NotFound(format!("Unknown undo id: {}", undo_id)));
+    }
+    let dir = undo_dir(&app)?.join(&undo_id);
+    let manifest: UndoManifest = fs::read(dir.join("manifest.json"))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| AppError::NotFound(format!("Unknown undo id: {}", undo_id)))?;
+
+    let mut writes = Vec::new();
+    for entry in &manifest.files {
+        let path = roots.resolve(&entry.file)?;
+        let current = fs::read(&path)?;
+        if file_io::content_hash(&current) != entry.replaced_hash {
+            return Err(AppError::Conflict(format!(
+                "{} was edited after the replace",
+                entry.file
+            )));
+        }
+        let original = fs::read(dir.join(&entry.backup))?;
+        writes.push((path, current, original));
+    }
+
+    write_all_or_rollback(&writes)?;
+    let _ = fs::remove_dir_all(&dir);
+
+    Ok(writes
+        .iter()
+        .map(|(path, _, _)| path.to_string_lossy().to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multiline_regex() -> SearchOptions {
+        SearchOptions {
+            regex: true,
+            multiline: true,
+            ..SearchOptions::default()
+        }
+    }
+
+    #[test]
+    fn preview_covers_a_match_ending_with_a_line_break() {
+        let options = multiline_regex();
+        let matcher = build_matcher("foo\\n", &options).unwrap();
+        let content = "foo\nbar";
+        let edits = plan_edits(content, &matcher, &options, "baz ");
+        assert_eq!(edits.len(), 1);
+
+        let indexed: Vec<_> = edits.iter().enumerate().collect();
+        let preview = preview_file("a.txt", content, String::new(), &indexed);
+        assert_eq!(preview.matches[0].before, "foo\nbar");
+        assert_eq!(preview.matches[0].after, "baz bar");
+        assert_eq!(preview.diff, "@@ -1,2 +1,1 @@\n-foo\n-bar\n+baz bar\n");
+    }
+
+    #[test]
+    fn preview_covers_a_line_break_at_the_end_of_the_file() {
+        let options = multiline_regex();
+        let matcher = build_matcher("bar\\n", &options).unwrap();
+        let content = "foo\nbar\n";
+        let edits = plan_edits(content, &matcher, &options, "");
+
+        let indexed: Vec<_> = edits.iter().enumerate().collect();
+        let preview = preview_file("a.txt", content, String::new(), &indexed);
+        assert_eq!(preview.matches[0].before, "bar\n");
+        assert_eq!(preview.matches[0].after, "");
+    }
+}
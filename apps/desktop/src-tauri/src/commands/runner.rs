@@ -1,11 +1,26 @@
-use crate::errors::AppResult;
-use tauri::{AppHandle, Emitter};
+use crate::errors::{AppError, AppResult};
+use crate::sandbox::WorkspaceRoots;
+use tauri::{AppHandle, Emitter, State};
 use std::process::{Command, Stdio};
 use std::io::{BufRead, BufReader};
 use std::thread;
 
+/// Run a program in an open workspace, streaming its output as `runner-*` events.
+///
+/// Any program can run here, that is what the console is for, so this is
+/// not confined by `WorkspaceRoots` beyond its working directory.
 #[tauri::command]
-pub async fn run_command(app: AppHandle, command: String, args: Vec<String>, cwd: Option<String>) -> AppResult<String> {
+pub async fn run_command(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+    command: String,
+    args: Vec<String>,
+    cwd: Option<String>,
+) -> AppResult<String> {
+    let cwd = cwd.ok_or_else(|| {
+        AppError::PermissionDenied("Commands only run inside an open workspace".to_string())
+    })?;
+    let cwd = roots.resolve(&cwd)?;
     let task_id = uuid::Uuid::new_v4().to_string();
     let task_id_clone = task_id.clone();
     let app_handle = app.clone();
@@ -44,11 +59,9 @@ pub async fn run_command(app: AppHandle, command: String, args: Vec<String>, cwd
             .collect();
         
         // Add NODE_PATH to help npm find modules
-        if let Some(ref dir) = cwd {
-            let node_modules = std::path::Path::new(dir).join("node_modules");
-            if node_modules.exists() {
-                filtered_env.insert("NODE_PATH".to_string(), node_modules.to_string_lossy().to_string());
-            }
+        let node_modules = cwd.join("node_modules");
+        if node_modules.exists() {
+            filtered_env.insert("NODE_PATH".to_string(), node_modules.to_string_lossy().to_string());
         }
         
         cmd.envs(filtered_env);
@@ -62,9 +75,7 @@ pub async fn run_command(app: AppHandle, command: String, args: Vec<String>, cwd
             cmd.creation_flags(CREATE_NO_WINDOW);
         }
 
-        if let Some(path) = cwd {
-            cmd.current_dir(path);
-        }
+        cmd.current_dir(&cwd);
 
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
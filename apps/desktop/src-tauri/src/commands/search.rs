@@ -1,15 +1,81 @@
-use crate::errors::AppResult;
+use crate::archive;
+use crate::errors::{AppError, AppResult};
+use crate::file_index::{FileIndex, FileMatch};
+use crate::file_io;
+use crate::models::Workspace;
+use crate::sandbox::WorkspaceRoots;
+use crate::trigram_index::{self, SearchIndexes};
+use crate::workspace::WorkspaceManager;
 use glob::Pattern;
-use serde::Serialize;
+use ignore::WalkBuilder;
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::Path;
-use walkdir::WalkDir;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[derive(Serialize, Clone)]
 pub struct SearchResult {
-    file: String,
     line: usize,
-    content: String,
+    end_line: usize, // differs from `line` only for multiline matches
+    content: String, // the whole line, leading whitespace included
+    matches: Vec<MatchRange>,
+    before: Vec<String>, // context lines, never repeating lines of a neighbouring result
+    after: Vec<String>,
+}
+
+/// The results of one file
+#[derive(Serialize, Clone)]
+pub struct FileResults {
+    file: String,
+    match_count: usize, // every match in the file, including those past the result cap
+    results: Vec<SearchResult>,
+}
+
+/// Column range of a match within `SearchResult::content`.
+///
+/// Columns are UTF-16 code units so the frontend can slice the string as-is.
+/// `groups` holds the ranges of the regex capture groups, `None` for groups
+/// that did not participate.
+#[derive(Serialize, Clone, Debug)]
+pub struct MatchRange {
+    start: usize,
+    end: usize,
+    groups: Vec<Option<(usize, usize)>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub multiline: bool,       // let matches span line breaks
+    pub include_ignored: bool, // also search files skipped by .gitignore and the exclude setting
+    pub context_lines: usize,  // lines of context before and after each result
+    pub search_archives: bool, // also look inside APKs, app bundles, JARs and zips
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            regex: false,
+            case_sensitive: true,
+            whole_word: false,
+            multiline: false,
+            include_ignored: false,
+            context_lines: 0,
+            search_archives: false,
+        }
+    }
 }
 
 /// Parse comma-separated glob patterns into a Vec of Pattern
@@ -48,100 +114,885 @@ fn matches_any_pattern(file_path: &Path, patterns: &[Pattern]) -> bool {
     })
 }
 
+/// Excluded from search when the workspace has no `searchExclude` setting
+const DEFAULT_SEARCH_EXCLUDE: &[&str] = &[
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    ".svelte-kit",
+    ".next",
+    "__pycache__",
+];
+
+/// Glob patterns excluded from search in the workspace containing `root`.
+///
+/// Read from the `searchExclude` string array of the workspace settings.
+pub(crate) fn workspace_exclude(manager: &WorkspaceManager, root: &Path) -> Vec<String> {
+    manager
+        .settings_for(root)
+        .and_then(|settings| serde_json::from_value(settings.get("searchExclude")?.clone()).ok())
+        .unwrap_or_else(|| {
+            DEFAULT_SEARCH_EXCLUDE
+                .iter()
+                .map(|p| p.to_string())
+                .collect()
+        })
+}
+
+/// Whether `path` inside `root` matches one of the `skip` globs, by name or relative path
+pub(crate) fn is_skipped(root: &Path, path: &Path, skip: &[Pattern]) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    skip.iter()
+        .any(|p| p.matches(&name) || p.matches(&relative))
+}
+
+/// Walk `root`, yielding the directories and files a search should look at.
+///
+/// `.gitignore`, `.ignore` and the `skip` globs prune the walk unless
+/// `include_ignored` is set. `.git` is never entered.
+pub(crate) fn walk_workspace(root: &Path, skip: &[String], include_ignored: bool) -> ignore::Walk {
+    let skip_patterns: Vec<Pattern> = skip.iter().filter_map(|p| Pattern::new(p).ok()).collect();
+    let walk_root = root.to_path_buf();
+
+    WalkBuilder::new(root)
+        .hidden(false)
+        .ignore(!include_ignored)
+        .git_ignore(!include_ignored)
+        .git_global(!include_ignored)
+        .git_exclude(!include_ignored)
+        .parents(!include_ignored)
+        .require_git(false)
+        .filter_entry(move |entry| {
+            if entry.file_name() == ".git" {
+                return false;
+            }
+            include_ignored
+                || entry.depth() == 0
+                || !is_skipped(&walk_root, entry.path(), &skip_patterns)
+        })
+        .build()
+}
+
+/// Walk `root` and yield the files a search should look at.
+///
+/// See `walk_workspace` for the ignore rules. The include/exclude globs
+/// typed in the search panel apply on top.
+pub(crate) fn candidate_files(
+    root: &Path,
+    include: &Option<String>,
+    exclude: &Option<String>,
+    skip: &[String],
+    include_ignored: bool,
+) -> impl Iterator<Item = PathBuf> {
+    let include_patterns = parse_patterns(include);
+    let exclude_patterns = parse_patterns(exclude);
+
+    walk_workspace(root, skip, include_ignored)
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(move |file_path| {
+            // Apply include filter: if patterns exist, file must match at least one
+            if !include_patterns.is_empty() && !matches_any_pattern(file_path, &include_patterns) {
+                return false;
+            }
+
+            // Apply exclude filter: skip if matches any exclude pattern
+            !matches_any_pattern(file_path, &exclude_patterns)
+        })
+}
+
+/// Read a whole file as UTF-8 text, `None` if it looks binary or is not valid UTF-8
+pub(crate) fn read_text(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    if file_io::is_binary(&bytes) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Pieces of `content` a query is matched against, with their byte offset.
+///
+/// Without multiline every line (minus its line break) is matched on its
+/// own; with it the whole content is one piece.
+pub(crate) fn match_segments(content: &str, multiline: bool) -> Vec<(usize, &str)> {
+    if multiline {
+        return vec![(0, content)];
+    }
+
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for line in content.split('\n') {
+        segments.push((start, line));
+        start += line.len() + 1;
+    }
+    segments
+}
+
+/// Compile the query into a regex honouring the search options
+pub(crate) fn build_matcher(query: &str, options: &SearchOptions) -> AppResult<Regex> {
+    let mut pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    if options.whole_word {
+        // `\b` next to a non-word character would demand a word character
+        // beside the match, so `-foo` or `foo(` could never be found
+        let (start, end) = regex_syntax::parse(&pattern)
+            .map(|hir| (word_edge(&hir, true), word_edge(&hir, false)))
+            .unwrap_or((true, true));
+        pattern = format!(
+            "{}(?:{}){}",
+            if start { r"\b" } else { "" },
+            pattern,
+            if end { r"\b" } else { "" }
+        );
+    }
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .crlf(true)
+        .dot_matches_new_line(options.multiline)
+        .build()
+        .map_err(|e| AppError::NotSupported(format!("Invalid regular expression: {}", e)))
+}
+
+/// Whether the first (or last) character matched by `hir` can be a word character
+fn word_edge(hir: &Hir, first: bool) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => false,
+        HirKind::Literal(literal) => {
+            let text = String::from_utf8_lossy(&literal.0);
+            let edge = if first {
+                text.chars().next()
+            } else {
+                text.chars().next_back()
+            };
+            edge.is_some_and(regex_syntax::is_word_character)
+        }
+        HirKind::Class(Class::Unicode(class)) => {
+            let Ok(word) = regex_syntax::parse(r"\w") else {
+                return true;
+            };
+            let HirKind::Class(Class::Unicode(word)) = word.into_kind() else {
+                return true;
+            };
+            let mut overlap = class.clone();
+            overlap.intersect(&word);
+            !overlap.ranges().is_empty()
+        }
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .any(|r| (r.start()..=r.end()).any(|b| b.is_ascii_alphanumeric() || b == b'_')),
+        HirKind::Repetition(repetition) => word_edge(&repetition.sub, first),
+        HirKind::Capture(capture) => word_edge(&capture.sub, first),
+        HirKind::Concat(parts) => {
+            // A part that can match nothing, like an anchor or `-?`, lets the
+            // next one provide the edge
+            let mut parts: Box<dyn Iterator<Item = &Hir>> = if first {
+                Box::new(parts.iter())
+            } else {
+                Box::new(parts.iter().rev())
+            };
+            parts
+                .find(|p| word_edge(p, first) || p.properties().minimum_len() != Some(0))
+                .is_some_and(|p| word_edge(p, first))
+        }
+        HirKind::Alternation(alternatives) => alternatives.iter().any(|a| word_edge(a, first)),
+    }
+}
+
+/// Number of UTF-16 code units in `text`
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// Byte range of a match and its capture groups, relative to the file content
+struct RawMatch {
+    start: usize,
+    end: usize,
+    groups: Vec<Option<(usize, usize)>>,
+}
+
+impl RawMatch {
+    fn from_captures(caps: &regex::Captures, base: usize) -> Self {
+        let whole = caps.get(0).unwrap();
+        RawMatch {
+            start: base + whole.start(),
+            end: base + whole.end(),
+            groups: caps
+                .iter()
+                .skip(1)
+                .map(|g| g.map(|g| (base + g.start(), base + g.end())))
+                .collect(),
+        }
+    }
+}
+
+/// Find every match of `matcher` in a file's content.
+///
+/// `first_line` is the 1-based line number of the start of `content`.
+/// Without multiline, each line is matched on its own. With it, the whole
+/// content is matched and a match is reported on the line where it starts,
+/// clipped to that line.
+fn search_content(
+    first_line: usize,
+    content: &str,
+    matcher: &Regex,
+    multiline: bool,
+    limit: usize,
+    results: &mut Vec<SearchResult>,
+) {
+    let mut line_starts = vec![0];
+    line_starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+    let line_of = |offset: usize| line_starts.partition_point(|&s| s <= offset) - 1;
+    let line_range = |idx: usize| {
+        let end = line_starts
+            .get(idx + 1)
+            .map(|&next| next - 1)
+            .unwrap_or(content.len());
+        line_starts[idx]..end
+    };
+
+    let mut push = |line_idx: usize, end_line_idx: usize, raw: Vec<RawMatch>| {
+        let range = line_range(line_idx);
+        let start = range.start;
+        let line_text = content[range].trim_end_matches('\r');
+
+        // Byte offset in the file -> UTF-16 column in the line
+        let column = |offset: usize| {
+            let in_line = offset.saturating_sub(start).min(line_text.len());
+            utf16_len(&line_text[..in_line])
+        };
+
+        let matches = raw
+            .iter()
+            .map(|m| MatchRange {
+                start: column(m.start),
+                end: column(m.end),
+                groups: m
+                    .groups
+                    .iter()
+                    .map(|g| g.map(|(s, e)| (column(s), column(e))))
+                    .collect(),
+            })
+            .collect();
+
+        results.push(SearchResult {
+            line: first_line + line_idx,
+            end_line: first_line + end_line_idx,
+            content: line_text.to_string(),
+            matches,
+            before: Vec::new(),
+            after: Vec::new(),
+        });
+        results.len()
+    };
+
+    for (base, segment) in match_segments(content, multiline) {
+        let raw: Vec<_> = matcher
+            .captures_iter(segment)
+            .map(|caps| RawMatch::from_captures(&caps, base))
+            .collect();
+
+        if multiline {
+            for m in raw {
+                let last_byte = m.end.saturating_sub(1).max(m.start);
+                if push(line_of(m.start), line_of(last_byte), vec![m]) >= limit {
+                    return;
+                }
+            }
+        } else if !raw.is_empty() {
+            let line_idx = line_of(base);
+            if push(line_idx, line_idx, raw) >= limit {
+                return;
+            }
+        }
+    }
+}
+
+/// Multiline search needs the whole file in memory, larger files are skipped
+const MAX_MULTILINE_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Default cap on the number of results of one search
+const DEFAULT_MAX_RESULTS: usize = 200;
+
+/// Default number of files returned by `find_files`
+const DEFAULT_MAX_FILE_MATCHES: usize = 50;
+
+/// Number of results sent per `search-result` event
+const RESULT_BATCH_SIZE: usize = 50;
+
+/// Fill in the context of results found by a whole-file search.
+///
+/// After-context stops at the next result and before-context starts after
+/// the previous result's context, so no line is shown twice.
+fn attach_context(content: &str, results: &mut [SearchResult], context: usize) {
+    if context == 0 {
+        return;
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    // 1-based, `to` excluded, clamped to the lines that exist
+    let text = |from: usize, to: usize| -> Vec<String> {
+        let to = to.min(lines.len() + 1);
+        lines[(from - 1).min(to - 1)..to - 1]
+            .iter()
+            .map(|l| l.to_string())
+            .collect()
+    };
+
+    for i in 0..results.len() {
+        let end = results[i].end_line;
+        let next = results.get(i + 1).map_or(lines.len() + 1, |r| r.line);
+        let stop = (end + 1 + context).min(next).max(end + 1);
+        results[i].after = text(end + 1, stop);
+
+        let line = results[i].line;
+        let prev_end = i
+            .checked_sub(1)
+            .map_or(0, |p| results[p].end_line + results[p].after.len());
+        let start = line.saturating_sub(context).max(prev_end + 1).min(line);
+        results[i].before = text(start, line);
+    }
+}
+
+/// Search one file, keeping the first `limit` results.
+///
+/// Returns the results with the number of matches in the whole file, which
+/// is larger than those of the results when the limit was hit. Line-by-line
+/// search streams the file instead of loading it at once, and lines that are
+/// not valid UTF-8 are decoded lossily. Files whose first bytes look binary
+/// yield no results.
+fn search_file(
+    path: &Path,
+    matcher: &Regex,
+    options: &SearchOptions,
+    limit: usize,
+) -> (Vec<SearchResult>, usize) {
+    let mut results = Vec::new();
+
+    if options.multiline {
+        let too_big = fs::metadata(path).map_or(true, |m| m.len() > MAX_MULTILINE_FILE_SIZE);
+        let mut match_count = 0;
+        if let (false, Some(content)) = (too_big, read_text(path)) {
+            search_content(1, &content, matcher, true, limit, &mut results);
+            attach_context(&content, &mut results, options.context_lines);
+            match_count = matcher.find_iter(&content).count();
+        }
+        return (results, match_count);
+    }
+
+    let Ok(handle) = fs::File::open(path) else {
+        return (results, 0);
+    };
+    let mut reader = BufReader::new(handle);
+    let mut head = Vec::new();
+    if (&mut reader).take(8192).read_to_end(&mut head).is_err() || file_io::is_binary(&head) {
+        return (results, 0);
+    }
+    search_lines(
+        head.as_slice().chain(reader),
+        matcher,
+        options.context_lines,
+        limit,
+    )
+}
+
+/// Search content that is already in memory, such as an archive entry
+fn search_bytes(
+    bytes: &[u8],
+    matcher: &Regex,
+    options: &SearchOptions,
+    limit: usize,
+) -> (Vec<SearchResult>, usize) {
+    let mut results = Vec::new();
+    if file_io::is_binary(bytes) {
+        return (results, 0);
+    }
+
+    if options.multiline {
+        let mut match_count = 0;
+        if let Ok(content) = std::str::from_utf8(bytes) {
+            search_content(1, content, matcher, true, limit, &mut results);
+            attach_context(content, &mut results, options.context_lines);
+            match_count = matcher.find_iter(content).count();
+        }
+        return (results, match_count);
+    }
+    search_lines(bytes, matcher, options.context_lines, limit)
+}
+
+/// Match `reader` line by line, collecting up to `context` lines around each
+/// result. Past `limit` results the remaining lines are only counted.
+fn search_lines(
+    mut reader: impl BufRead,
+    matcher: &Regex,
+    context: usize,
+    limit: usize,
+) -> (Vec<SearchResult>, usize) {
+    let mut results = Vec::new();
+    let mut match_count = 0;
+    let mut buf = Vec::new();
+    let mut line_no = 0;
+    // Lines since the last result that may become before-context
+    let mut recent: VecDeque<String> = VecDeque::with_capacity(context);
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => line_no += 1,
+        }
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+
+        let found = results.len();
+        if found < limit && matcher.is_match(line) {
+            search_content(line_no, line, matcher, false, limit, &mut results);
+            if let Some(result) = results.get_mut(found) {
+                result.before = recent.drain(..).collect();
+                match_count += result.matches.len();
+            }
+            continue;
+        }
+        if found >= limit {
+            match_count += matcher.find_iter(line).count();
+        }
+
+        // Past the limit, lines are only needed to complete the last result's context
+        let complete = found >= limit && results.last().map_or(true, |r| r.after.len() >= context);
+        if context > 0 && !complete {
+            let line = line.trim_end_matches('\r').to_string();
+            match results.last_mut() {
+                Some(last) if last.after.len() < context && recent.is_empty() => {
+                    last.after.push(line)
+                }
+                _ => {
+                    if recent.len() == context {
+                        recent.pop_front();
+                    }
+                    recent.push_back(line);
+                }
+            }
+        }
+    }
+    (results, match_count)
+}
+
+/// Outcome of a search run
+#[derive(Serialize, Clone, Debug)]
+pub struct SearchSummary {
+    files_scanned: usize,
+    result_count: usize,
+    elapsed_ms: u64,
+    truncated: bool, // more results exist than the cap let through
+    cancelled: bool,
+}
+
+/// Everything needed to run one search, validated up front
+struct SearchRequest {
+    root: PathBuf,
+    entry_prefix: Option<String>, // folder inside the root when the root is an archive
+    include: Option<String>,
+    exclude: Option<String>,
+    skip: Vec<String>, // the workspace exclude setting
+    matcher: Regex,
+    options: SearchOptions,
+    max_results: usize,
+    ruled_out: HashSet<PathBuf>, // files the trigram index proved cannot match
+}
+
+/// Files the workspace's trigram index rules out, empty if it does not use one
+fn index_exclusions(
+    app: &AppHandle,
+    workspace: Option<Workspace>,
+    query: &str,
+    request: &SearchRequest,
+) -> HashSet<PathBuf> {
+    let Some(workspace) = workspace.filter(trigram_index::is_enabled) else {
+        return HashSet::new();
+    };
+    if request.options.include_ignored {
+        return HashSet::new(); // the index only covers files that are not ignored
+    }
+
+    let indexes = app.state::<SearchIndexes>();
+    match indexes.excluded_files(app, &workspace, &request.skip, query, &request.options) {
+        Ok(excluded) => excluded,
+        Err(e) => {
+            log::warn!("Search index unavailable, searching every file: {:?}", e);
+            HashSet::new()
+        }
+    }
+}
+
+/// Search the files under the request root in parallel.
+///
+/// Results are handed to `on_batch` in groups of `RESULT_BATCH_SIZE` as they
+/// are found, in no particular order. The run stops early once more than
+/// `max_results` are found or `cancel` is set.
+fn run_search<F>(request: &SearchRequest, cancel: &AtomicBool, on_batch: F) -> SearchSummary
+where
+    F: Fn(Vec<FileResults>) + Sync,
+{
+    let SearchRequest {
+        root,
+        entry_prefix,
+        include,
+        exclude,
+        skip,
+        matcher,
+        options,
+        max_results,
+        ruled_out,
+    } = request;
+    let max_results = *max_results;
+
+    let started = Instant::now();
+    let files_scanned = AtomicUsize::new(0);
+    let result_count = AtomicUsize::new(0);
+    let truncated = AtomicBool::new(false);
+    // Files waiting to be sent, with their total number of results
+    let pending = Mutex::new((Vec::new(), 0));
+
+    let stop = || cancel.load(Ordering::Relaxed) || truncated.load(Ordering::Relaxed);
+
+    let include_patterns = parse_patterns(include);
+    let exclude_patterns = parse_patterns(exclude);
+    let wanted = |path: &Path| {
+        (include_patterns.is_empty() || matches_any_pattern(path, &include_patterns))
+            && !matches_any_pattern(path, &exclude_patterns)
+    };
+    // Archives are opened when asked to, or when the search path is one
+    let descend = |path: &Path| {
+        archive::is_archive(path) && (options.search_archives || path == root.as_path())
+    };
+    let prefix = entry_prefix.as_ref().map(|p| format!("{}/", p));
+    let in_prefix = |name: &str| {
+        prefix.as_deref().map_or(true, |p| {
+            name.starts_with(p) || Some(name) == entry_prefix.as_deref()
+        })
+    };
+
+    let report = |file: String, (mut found, match_count): (Vec<SearchResult>, usize)| {
+        if found.is_empty() {
+            return;
+        }
+
+        // Reserve room under the cap before handing results out. Only a
+        // result past the cap means there are more than were returned.
+        let before = result_count.fetch_add(found.len(), Ordering::Relaxed);
+        if before + found.len() > max_results {
+            truncated.store(true, Ordering::Relaxed);
+            found.truncate(max_results.saturating_sub(before));
+            if found.is_empty() {
+                return;
+            }
+        }
+
+        let file = FileResults {
+            file,
+            match_count,
+            results: found,
+        };
+        let batch = {
+            let mut pending = pending.lock().unwrap();
+            pending.1 += file.results.len();
+            pending.0.push(file);
+            if pending.1 < RESULT_BATCH_SIZE {
+                return;
+            }
+            std::mem::take(&mut *pending).0
+        };
+        on_batch(batch);
+    };
+
+    // Include globs are applied here rather than by the walk so they can
+    // match the entries of an archive instead of the archive itself
+    candidate_files(root, &None, exclude, skip, options.include_ignored)
+        .filter(|path| descend(path) || (wanted(path) && !ruled_out.contains(path)))
+        .par_bridge()
+        .for_each(|path| {
+            if stop() {
+                return;
+            }
+            if !descend(&path) {
+                files_scanned.fetch_add(1, Ordering::Relaxed);
+                let found = search_file(&path, matcher, options, max_results + 1);
+                report(path.to_string_lossy().to_string(), found);
+                return;
+            }
+
+            let searched = archive::for_each_entry(
+                &path,
+                |name| in_prefix(name) && wanted(Path::new(name)),
+                |name, bytes| {
+                    if stop() {
+                        return false;
+                    }
+                    files_scanned.fetch_add(1, Ordering::Relaxed);
+                    let found = search_bytes(&bytes, matcher, options, max_results + 1);
+                    report(archive::entry_path(&path, name), found);
+                    true
+                },
+            );
+            if let Err(e) = searched {
+                log::warn!("Cannot search {}: {:?}", path.display(), e);
+            }
+        });
+
+    let (rest, _) = std::mem::take(&mut *pending.lock().unwrap());
+    if !rest.is_empty() {
+        on_batch(rest);
+    }
+
+    SearchSummary {
+        files_scanned: files_scanned.load(Ordering::Relaxed),
+        result_count: result_count.load(Ordering::Relaxed).min(max_results),
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        truncated: truncated.load(Ordering::Relaxed),
+        cancelled: cancel.load(Ordering::Relaxed),
+    }
+}
+
+/// Resolve a search path, which may point into an archive (`app.apk!/assets`)
+fn search_root(roots: &WorkspaceRoots, path: &str) -> AppResult<(PathBuf, Option<String>)> {
+    match archive::split(path) {
+        Some((outer, entry)) => Ok((
+            roots.resolve(outer)?,
+            Some(entry.to_string()).filter(|e| !e.is_empty()),
+        )),
+        None => Ok((roots.resolve(path)?, None)),
+    }
+}
+
+/// Results of a blocking search, grouped by file in path order
+#[derive(Serialize)]
+pub struct SearchResponse {
+    files: Vec<FileResults>,
+    summary: SearchSummary,
+}
+
+/// Cancellation flags of the searches currently running, keyed by search id
+#[derive(Default)]
+pub struct SearchRegistry {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_in_files(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+    manager: State<'_, WorkspaceManager>,
     query: String,
     path: String,
     include: Option<String>,
     exclude: Option<String>,
-) -> AppResult<Vec<SearchResult>> {
-    let mut results = Vec::new();
+    options: Option<SearchOptions>,
+    max_results: Option<usize>,
+) -> AppResult<SearchResponse> {
+    let (root, entry_prefix) = search_root(&roots, &path)?;
+    let options = options.unwrap_or_default();
+    let matcher = build_matcher(&query, &options)?;
 
     log::info!(
-        "Searching for '{}' in '{}' (include: {:?}, exclude: {:?})",
+        "Searching for '{}' in '{}' (include: {:?}, exclude: {:?}, options: {:?})",
         query,
         path,
         include,
-        exclude
+        exclude,
+        options
     );
 
-    // Parse glob patterns
-    let include_patterns = parse_patterns(&include);
-    let exclude_patterns = parse_patterns(&exclude);
-
-    // Directories to always skip
-    let skip_dirs = [
-        "node_modules",
-        ".git",
-        "target",
-        "dist",
-        "build",
-        ".svelte-kit",
-        ".next",
-        "__pycache__",
-    ];
-
-    // Binary extensions to skip
-    let binary_exts = [
-        "png", "jpg", "jpeg", "gif", "ico", "icns", "woff", "woff2", "ttf", "eot", "exe", "dll",
-        "so", "dylib", "pdf", "zip", "tar", "gz", "webp", "svg", "mp3", "mp4", "wav", "avi",
-    ];
-
-    for entry in WalkDir::new(&path)
-        .into_iter()
-        .filter_entry(|e| {
-            let name = e.file_name().to_string_lossy();
-            !skip_dirs.iter().any(|d| name == *d)
-        })
-        .filter_map(|e| e.ok())
-    {
-        if !entry.file_type().is_file() {
-            continue;
-        }
+    let workspace = manager.containing(&root);
+    let mut request = SearchRequest {
+        skip: workspace_exclude(&manager, &root),
+        root,
+        entry_prefix,
+        include,
+        exclude,
+        matcher,
+        options,
+        max_results: max_results.unwrap_or(DEFAULT_MAX_RESULTS),
+        ruled_out: HashSet::new(),
+    };
+    request.ruled_out = index_exclusions(&app, workspace, &query, &request);
+    let files = Mutex::new(Vec::new());
+    let summary = run_search(&request, &AtomicBool::new(false), |batch| {
+        files.lock().unwrap().extend(batch)
+    });
 
-        let file_path = entry.path();
+    let mut files = files.into_inner().unwrap();
+    files.sort_by(|a, b| a.file.cmp(&b.file));
 
-        // Skip binary files by extension
-        let ext = file_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("");
-        if binary_exts.contains(&ext.to_lowercase().as_str()) {
-            continue;
-        }
+    log::info!("Found results in {} files ({:?})", files.len(), summary);
+    Ok(SearchResponse { files, summary })
+}
 
-        // Apply include filter: if patterns exist, file must match at least one
-        if !include_patterns.is_empty() && !matches_any_pattern(file_path, &include_patterns) {
-            continue;
-        }
+/// Start a search in the background and return its id.
+///
+/// Results arrive grouped by file as `search-result` events
+/// (`{ searchId, files }`) and the run ends with a `search-complete` event carrying the `SearchSummary`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn start_search(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+    manager: State<'_, WorkspaceManager>,
+    registry: State<'_, SearchRegistry>,
+    query: String,
+    path: String,
+    include: Option<String>,
+    exclude: Option<String>,
+    options: Option<SearchOptions>,
+    max_results: Option<usize>,
+) -> AppResult<String> {
+    let options = options.unwrap_or_default();
+    let (root, entry_prefix) = search_root(&roots, &path)?;
+    let workspace = manager.containing(&root);
+    let mut request = SearchRequest {
+        skip: workspace_exclude(&manager, &root),
+        root,
+        entry_prefix,
+        include,
+        exclude,
+        matcher: build_matcher(&query, &options)?,
+        options,
+        max_results: max_results.unwrap_or(DEFAULT_MAX_RESULTS),
+        ruled_out: HashSet::new(),
+    };
 
-        // Apply exclude filter: skip if matches any exclude pattern
-        if matches_any_pattern(file_path, &exclude_patterns) {
-            continue;
-        }
+    let search_id = uuid::Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+    registry
+        .running
+        .lock()
+        .unwrap()
+        .insert(search_id.clone(), cancel.clone());
 
-        let file_path_str = file_path.to_string_lossy().to_string();
+    let id = search_id.clone();
+    thread::spawn(move || {
+        // Updating the index can take a while, so it happens off the command
+        request.ruled_out = index_exclusions(&app, workspace, &query, &request);
+        let summary = run_search(&request, &cancel, |batch| {
+            let _ = app.emit(
+                "search-result",
+                serde_json::json!({ "searchId": id, "files": batch }),
+            );
+        });
 
-        if let Ok(content) = fs::read_to_string(file_path) {
-            for (i, line) in content.lines().enumerate() {
-                if line.contains(&query) {
-                    results.push(SearchResult {
-                        file: file_path_str.clone(),
-                        line: i + 1,
-                        content: line.trim().to_string(),
-                    });
-                    if results.len() >= 200 {
-                        break;
-                    }
-                }
-            }
+        app.state::<SearchRegistry>()
+            .running
+            .lock()
+            .unwrap()
+            .remove(&id);
+        log::info!("Search {} finished: {:?}", id, summary);
+        let _ = app.emit(
+            "search-complete",
+            serde_json::json!({ "searchId": id, "summary": summary }),
+        );
+    });
+
+    Ok(search_id)
+}
+
+#[tauri::command]
+pub async fn cancel_search(
+    registry: State<'_, SearchRegistry>,
+    search_id: String,
+) -> AppResult<()> {
+    if let Some(cancel) = registry.running.lock().unwrap().get(&search_id) {
+        cancel.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Fuzzy-find files by path across all roots of a workspace ("Go to file")
+#[tauri::command]
+pub async fn find_files(
+    manager: State<'_, WorkspaceManager>,
+    index: State<'_, FileIndex>,
+    workspace_id: String,
+    query: String,
+    limit: Option<usize>,
+) -> AppResult<Vec<FileMatch>> {
+    let workspace = manager.get(&workspace_id)?;
+    let roots: Vec<PathBuf> = workspace.roots.iter().map(PathBuf::from).collect();
+    let skip = workspace_exclude(&manager, &roots[0]);
+
+    Ok(index.find(
+        &workspace_id,
+        &roots,
+        &skip,
+        &query,
+        limit.unwrap_or(DEFAULT_MAX_FILE_MATCHES),
+    ))
+}
+
+/// Build or update the trigram index of a workspace in the background.
+///
+/// Progress arrives as `search-index-progress` events, the index is used
+/// by searches once the workspace setting `searchIndex` is true.
+#[tauri::command]
+pub async fn rebuild_search_index(
+    app: AppHandle,
+    manager: State<'_, WorkspaceManager>,
+    workspace_id: String,
+) -> AppResult<()> {
+    let workspace = manager.get(&workspace_id)?;
+    let skip = workspace_exclude(&manager, Path::new(&workspace.roots[0]));
+
+    thread::spawn(move || {
+        let indexes = app.state::<SearchIndexes>();
+        if let Err(e) = indexes.update(&app, &workspace, &skip) {
+            log::error!("Failed to index workspace {}: {:?}", workspace.id, e);
         }
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_search_index(
+    app: AppHandle,
+    indexes: State<'_, SearchIndexes>,
+    workspace_id: String,
+) -> AppResult<()> {
+    indexes.delete(&app, &workspace_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if results.len() >= 200 {
-            break;
+    fn whole_word(regex: bool) -> SearchOptions {
+        SearchOptions {
+            regex,
+            whole_word: true,
+            ..SearchOptions::default()
         }
     }
 
-    log::info!("Found {} results", results.len());
-    Ok(results)
+    #[test]
+    fn whole_word_only_bounds_word_edges() {
+        let matcher = build_matcher("-foo(", &whole_word(false)).unwrap();
+        assert!(matcher.is_match("a -foo(b)"));
+        assert!(!matcher.is_match("a -foobar("));
+
+        let matcher = build_matcher("foo", &whole_word(false)).unwrap();
+        assert!(matcher.is_match("a foo b"));
+        assert!(!matcher.is_match("foobar"));
+
+        let matcher = build_matcher(r"\$\w+", &whole_word(true)).unwrap();
+        assert_eq!(matcher.find("a$HOME.").map(|m| m.as_str()), Some("$HOME"));
+
+        let matcher = build_matcher("-?bar", &whole_word(true)).unwrap();
+        assert!(matcher.is_match("x -bar"));
+        assert!(!matcher.is_match("xbar"));
+    }
 }
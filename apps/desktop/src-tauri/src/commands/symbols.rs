@@ -0,0 +1,68 @@
+use crate::command_refs::{self, CommandReport};
+use crate::commands::search::workspace_exclude;
+use crate::errors::{AppError, AppResult};
+use crate::sandbox::WorkspaceRoots;
+use crate::symbols::{self, Symbol, SymbolIndex};
+use crate::workspace::WorkspaceManager;
+use std::path::PathBuf;
+use tauri::State;
+use tree_sitter::Parser;
+
+/// Default number of symbols returned by `search_symbols`
+const DEFAULT_MAX_SYMBOLS: usize = 100;
+
+/// Fuzzy-find Rust and TypeScript/Svelte symbols by name across a workspace
+#[tauri::command]
+pub async fn search_symbols(
+    manager: State<'_, WorkspaceManager>,
+    index: State<'_, SymbolIndex>,
+    workspace_id: String,
+    query: String,
+    limit: Option<usize>,
+) -> AppResult<Vec<Symbol>> {
+    let workspace = manager.get(&workspace_id)?;
+    let roots: Vec<PathBuf> = workspace.roots.iter().map(PathBuf::from).collect();
+    let skip = workspace_exclude(&manager, &roots[0]);
+
+    Ok(index.search(
+        &workspace_id,
+        &roots,
+        &skip,
+        &query,
+        limit.unwrap_or(DEFAULT_MAX_SYMBOLS),
+    ))
+}
+
+/// Symbols of one file in document order, for an outline view
+#[tauri::command]
+pub async fn document_symbols(
+    roots: State<'_, WorkspaceRoots>,
+    path: String,
+) -> AppResult<Vec<Symbol>> {
+    let path = roots.resolve(&path)?;
+    if !symbols::is_supported(&path) {
+        return Err(AppError::NotSupported(format!(
+            "No symbol support for {}",
+            path.display()
+        )));
+    }
+
+    let mut parser = Parser::new();
+    symbols::file_symbols(&mut parser, &path)
+        .ok_or_else(|| AppError::NotSupported(format!("Cannot parse {}", path.display())))
+}
+
+/// Cross-reference `#[tauri::command]`s with `generate_handler!` and frontend `invoke` calls
+#[tauri::command]
+pub async fn analyze_commands(
+    manager: State<'_, WorkspaceManager>,
+    index: State<'_, SymbolIndex>,
+    workspace_id: String,
+) -> AppResult<CommandReport> {
+    let workspace = manager.get(&workspace_id)?;
+    let roots: Vec<PathBuf> = workspace.roots.iter().map(PathBuf::from).collect();
+    let skip = workspace_exclude(&manager, &roots[0]);
+
+    let commands = index.filter(&workspace_id, &roots, &skip, |s| s.kind == "command");
+    Ok(command_refs::analyze(commands, &roots, &skip))
+}
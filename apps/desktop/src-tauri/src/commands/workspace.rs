@@ -0,0 +1,236 @@
+use crate::errors::{AppError, AppResult};
+use crate::file_index::FileIndex;
+use crate::models::Workspace;
+use crate::sandbox::{self, WorkspaceRoots};
+use crate::symbols::SymbolIndex;
+use crate::trigram_index::SearchIndexes;
+use crate::workspace::{self, WorkspaceManager};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+
+/// Canonical path of a folder the user picked in the folder dialog or
+/// opened before. Anything else would let the webview widen its own sandbox.
+fn trusted_root(app: &AppHandle, roots: &WorkspaceRoots, path: &str) -> AppResult<PathBuf> {
+    let root = sandbox::workspace_root(path)?;
+    let known = workspace::known_roots(app);
+    if roots.take_pick(&root) || known.iter().any(|k| Path::new(k) == root) {
+        Ok(root)
+    } else {
+        log::warn!("Blocked opening a folder the user did not pick: {}", path);
+        Err(AppError::PermissionDenied(format!(
+            "Open {} through the folder picker first",
+            path
+        )))
+    }
+}
+
+/// Approve a trusted root and return its canonical path.
+///
+/// Roots are not added to the asset protocol scope, previews go through the
+/// thumbnail cache so nothing has to be revoked when a root is removed.
+fn approve_root(roots: &WorkspaceRoots, root: PathBuf) -> String {
+    roots.add(root.clone());
+    root.to_string_lossy().to_string()
+}
+
+/// Revoke roots that no open workspace uses anymore
+fn release_roots(manager: &WorkspaceManager, roots: &WorkspaceRoots, released: &[String]) {
+    for root in released {
+        if !manager.holds_root(root) {
+            roots.remove(root);
+        }
+    }
+}
+
+/// Ask the user for a folder to open. The picked folder may then be passed
+/// to `open_workspace` or `add_workspace_root`.
+#[tauri::command]
+pub async fn pick_workspace_folder(
+    app: AppHandle,
+    roots: State<'_, WorkspaceRoots>,
+) -> AppResult<Option<String>> {
+    let Some(picked) = app.dialog().file().blocking_pick_folder() else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let root = sandbox::workspace_root(&path.to_string_lossy())?;
+    roots.remember_pick(root.clone());
+    Ok(Some(root.to_string_lossy().to_string()))
+}
+
+/// Open a workspace made of one or more folders.
+///
+/// Every folder must come from `pick_workspace_folder` or be a root of a
+/// workspace opened before.
+///
+/// Reopening the same set of folders restores the saved workspace with its
+/// id and settings. The primary root is moved to the front of the recent
+/// projects list.
+#[tauri::command]
+pub async fn open_workspace(
+    app: AppHandle,
+    manager: State<'_, WorkspaceManager>,
+    roots: State<'_, WorkspaceRoots>,
+    paths: Vec<String>,
+    name: Option<String>,
+) -> AppResult<Workspace> {
+    if paths.is_empty() {
+        return Err(AppError::NotSupported(
+            "A workspace needs at least one folder".into(),
+        ));
+    }
+
+    // Check every folder before approving any, so a bad path leaves no roots behind
+    let trusted = paths
+        .iter()
+        .map(|path| trusted_root(&app, &roots, path))
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let mut canonical = Vec::new();
+    for root in trusted {
+        let root = approve_root(&roots, root);
+        if !canonical.contains(&root) {
+            canonical.push(root);
+        }
+    }
+
+    let saved = workspace::load_saved(&app)
+        .into_iter()
+        .find(|w| w.roots == canonical);
+    let mut opened = saved.unwrap_or_else(|| Workspace {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: String::new(),
+        roots: canonical.clone(),
+        settings: serde_json::json!({}),
+    });
+    if let Some(name) = name {
+        opened.name = name;
+    }
+    if opened.name.is_empty() {
+        opened.name = Path::new(&canonical[0])
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| canonical[0].clone());
+    }
+
+    manager.insert(opened.clone());
+    workspace::save(&app, &opened)?;
+    workspace::push_recent_project(&app, &canonical[0])?;
+
+    log::info!("Opened workspace {} ({:?})", opened.id, opened.roots);
+    Ok(opened)
+}
+
+#[tauri::command]
+pub async fn close_workspace(
+    manager: State<'_, WorkspaceManager>,
+    roots: State<'_, WorkspaceRoots>,
+    index: State<'_, FileIndex>,
+    symbols: State<'_, SymbolIndex>,
+    search_indexes: State<'_, SearchIndexes>,
+    id: String,
+) -> AppResult<()> {
+    if let Some(closed) = manager.remove(&id) {
+        release_roots(&manager, &roots, &closed.roots);
+        index.forget(&id);
+        symbols.forget(&id);
+        search_indexes.forget(&id);
+        log::info!("Closed workspace {}", id);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_workspaces(manager: State<'_, WorkspaceManager>) -> AppResult<Vec<Workspace>> {
+    Ok(manager.list())
+}
+
+#[tauri::command]
+pub async fn add_workspace_root(
+    app: AppHandle,
+    manager: State<'_, WorkspaceManager>,
+    roots: State<'_, WorkspaceRoots>,
+    id: String,
+    path: String,
+) -> AppResult<Workspace> {
+    manager.get(&id)?;
+    let root = trusted_root(&app, &roots, &path)?;
+    let root = approve_root(&roots, root);
+
+    let updated = manager.update(&id, |w| {
+        if !w.roots.contains(&root) {
+            w.roots.push(root.clone());
+        }
+    })?;
+    workspace::save(&app, &updated)?;
+    Ok(updated)
+}
+
+/// Remove a folder from a workspace and drop the caches built over it.
+///
+/// Roots are stored canonical, so `path` is canonicalised the same way
+/// `add_workspace_root` does. A folder that no longer exists is matched as is.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn remove_workspace_root(
+    app: AppHandle,
+    manager: State<'_, WorkspaceManager>,
+    roots: State<'_, WorkspaceRoots>,
+    index: State<'_, FileIndex>,
+    symbols: State<'_, SymbolIndex>,
+    search_indexes: State<'_, SearchIndexes>,
+    id: String,
+    path: String,
+) -> AppResult<Workspace> {
+    let root = std::fs::canonicalize(&path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or(path);
+
+    let current = manager.get(&id)?;
+    if !current.roots.contains(&root) {
+        return Err(AppError::NotFound(format!(
+            "{} is not a folder of this workspace",
+            root
+        )));
+    }
+    if current.roots.len() == 1 {
+        return Err(AppError::NotSupported(
+            "Cannot remove the last folder, close the workspace instead".into(),
+        ));
+    }
+
+    let updated = manager.update(&id, |w| w.roots.retain(|r| *r != root))?;
+    release_roots(&manager, &roots, &[root]);
+    index.forget(&id);
+    symbols.forget(&id);
+    search_indexes.forget(&id);
+    workspace::save(&app, &updated)?;
+    Ok(updated)
+}
+
+/// Replace the per-workspace settings object
+#[tauri::command]
+pub async fn update_workspace_settings(
+    app: AppHandle,
+    manager: State<'_, WorkspaceManager>,
+    id: String,
+    settings: serde_json::Value,
+) -> AppResult<Workspace> {
+    if !settings.is_object() {
+        return Err(AppError::NotSupported(
+            "Workspace settings must be an object".into(),
+        ));
+    }
+
+    let updated = manager.update(&id, |w| w.settings = settings)?;
+    workspace::save(&app, &updated)?;
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn get_recent_projects(app: AppHandle) -> AppResult<Vec<String>> {
+    Ok(workspace::recent_projects(&app))
+}
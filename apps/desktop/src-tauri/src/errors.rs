@@ -14,6 +14,12 @@ pub enum AppError {
     NotFound(String),
     #[error("NotSupported: {0}")]
     NotSupported(String),
+    #[error("PermissionDenied: {0}")]
+    PermissionDenied(String),
+    #[error("AlreadyExists: {0}")]
+    AlreadyExists(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
 }
 
 impl From<std::io::Error> for AppError {
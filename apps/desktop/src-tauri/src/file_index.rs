@@ -0,0 +1,279 @@
+use crate::commands::search::{is_skipped, walk_workspace};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use glob::Pattern;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Number of recently opened files remembered for ranking
+const MAX_RECENT_FILES: usize = 50;
+
+/// Score added to a file that was opened recently, the most recent gets all of it
+const RECENT_BONUS: i64 = 100;
+
+/// Score removed per directory level, so shallow files win ties
+const DEPTH_PENALTY: i64 = 2;
+
+#[derive(Serialize, Clone)]
+pub struct FileMatch {
+    path: String,
+    relative: String, // shown in the finder, prefixed with the root name in multi-root workspaces
+    score: i64,
+    indices: Vec<usize>, // UTF-16 offsets of the matched characters in `relative`
+}
+
+struct IndexedFile {
+    path: String,
+    relative: String,
+    name_start: usize, // byte offset of the file name in `relative`
+    depth: usize,
+}
+
+/// File list of one workspace, rebuilt whenever the watcher reports a change
+struct WorkspaceIndex {
+    roots: Vec<PathBuf>,
+    skip: Vec<String>,
+    files: Vec<IndexedFile>,
+    dirty: Arc<AtomicBool>,
+    watched: HashSet<PathBuf>,
+    watcher: Option<RecommendedWatcher>,
+}
+
+impl WorkspaceIndex {
+    fn new(roots: Vec<PathBuf>, skip: Vec<String>) -> Self {
+        let dirty = Arc::new(AtomicBool::new(true));
+        let patterns: Vec<Pattern> = skip.iter().filter_map(|p| Pattern::new(p).ok()).collect();
+        let watched_roots = roots.clone();
+        let flag = dirty.clone();
+
+        // Only additions, removals, renames and edited ignore files change the file list
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            let ignore_file = event.paths.iter().any(|p| {
+                p.file_name()
+                    .is_some_and(|n| n == ".gitignore" || n == ".ignore")
+            });
+            let structural = ignore_file
+                || matches!(
+                    event.kind,
+                    EventKind::Create(_)
+                        | EventKind::Remove(_)
+                        | EventKind::Modify(notify::event::ModifyKind::Name(_))
+                );
+            if structural
+                && event
+                    .paths
+                    .iter()
+                    .any(|p| is_indexed_path(&watched_roots, p, &patterns))
+            {
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+        if let Err(e) = &watcher {
+            log::warn!(
+                "File watcher unavailable, the file index will not refresh: {}",
+                e
+            );
+        }
+
+        Self {
+            roots,
+            skip,
+            files: Vec::new(),
+            dirty,
+            watched: HashSet::new(),
+            watcher: watcher.ok(),
+        }
+    }
+
+    /// Walk the roots again and watch every directory that was walked.
+    ///
+    /// Directories are watched one level at a time so ignored trees such as
+    /// `node_modules` never use up watch handles.
+    fn rebuild(&mut self) {
+        self.dirty.store(false, Ordering::Relaxed);
+        let multi_root = self.roots.len() > 1;
+        let mut files = Vec::new();
+        let mut dirs = HashSet::new();
+
+        for root in &self.roots {
+            let prefix = if multi_root {
+                root.file_name()
+                    .map(|n| format!("{}/", n.to_string_lossy()))
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
+
+            for entry in walk_workspace(root, &self.skip, false).filter_map(|e| e.ok()) {
+                let Some(file_type) = entry.file_type() else {
+                    continue;
+                };
+                if file_type.is_dir() {
+                    dirs.insert(entry.into_path());
+                    continue;
+                }
+                if !file_type.is_file() {
+                    continue;
+                }
+
+                let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+                let relative = format!(
+                    "{}{}",
+                    prefix,
+                    relative.to_string_lossy().replace('\\', "/")
+                );
+                files.push(IndexedFile {
+                    path: entry.path().to_string_lossy().to_string(),
+                    name_start: relative.rfind('/').map_or(0, |i| i + 1),
+                    depth: entry.depth(),
+                    relative,
+                });
+            }
+        }
+
+        if let Some(watcher) = &mut self.watcher {
+            for gone in self.watched.difference(&dirs) {
+                let _ = watcher.unwatch(gone);
+            }
+            for dir in dirs.difference(&self.watched) {
+                if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("Cannot watch {}: {}", dir.display(), e);
+                }
+            }
+        }
+
+        log::info!("Indexed {} files in {:?}", files.len(), self.roots);
+        self.watched = dirs;
+        self.files = files;
+    }
+}
+
+/// Whether a path reported by the watcher can be part of the index
+fn is_indexed_path(roots: &[PathBuf], path: &Path, skip: &[Pattern]) -> bool {
+    let Some(root) = roots.iter().find(|r| path.starts_with(r)) else {
+        return false;
+    };
+    !path
+        .ancestors()
+        .take_while(|a| *a != root.as_path())
+        .any(|a| a.file_name().is_some_and(|n| n == ".git") || is_skipped(root, a, skip))
+}
+
+/// Convert char indices in `text` to UTF-16 offsets
+fn utf16_indices(text: &str, char_indices: &[usize]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(char_indices.len());
+    let mut wanted = char_indices.iter().peekable();
+    let mut offset = 0;
+    for (i, c) in text.chars().enumerate() {
+        if wanted.peek() == Some(&&i) {
+            offsets.push(offset);
+            wanted.next();
+        }
+        offset += c.len_utf16();
+    }
+    offsets
+}
+
+/// Cached file lists for quick open, keyed by workspace id
+#[derive(Default)]
+pub struct FileIndex {
+    workspaces: Mutex<HashMap<String, WorkspaceIndex>>,
+    recent: Mutex<Vec<String>>, // most recently opened first
+}
+
+impl FileIndex {
+    /// Fuzzy-match `query` against the file paths of a workspace.
+    ///
+    /// The index is built on first use and whenever the watcher saw files
+    /// come or go. Matches in the file name, recently opened files and
+    /// shallow paths rank higher. An empty query lists recent files.
+    pub fn find(
+        &self,
+        workspace_id: &str,
+        roots: &[PathBuf],
+        skip: &[String],
+        query: &str,
+        limit: usize,
+    ) -> Vec<FileMatch> {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let index = workspaces
+            .entry(workspace_id.to_string())
+            .or_insert_with(|| WorkspaceIndex::new(roots.to_vec(), skip.to_vec()));
+        if index.roots != roots || index.skip != skip {
+            *index = WorkspaceIndex::new(roots.to_vec(), skip.to_vec());
+        }
+        if index.dirty.load(Ordering::Relaxed) {
+            index.rebuild();
+        }
+
+        let recent = self.recent.lock().unwrap().clone();
+        let recency = |path: &str| {
+            recent.iter().position(|r| r == path).map_or(0, |i| {
+                RECENT_BONUS * (MAX_RECENT_FILES - i) as i64 / MAX_RECENT_FILES as i64
+            })
+        };
+
+        let query: String = query.chars().filter(|c| !c.is_whitespace()).collect();
+        if query.is_empty() {
+            return recent
+                .iter()
+                .filter_map(|path| index.files.iter().find(|f| f.path == *path))
+                .take(limit)
+                .map(|f| FileMatch {
+                    path: f.path.clone(),
+                    relative: f.relative.clone(),
+                    score: 0,
+                    indices: Vec::new(),
+                })
+                .collect();
+        }
+
+        let matcher = SkimMatcherV2::default();
+        let mut matches: Vec<FileMatch> = index
+            .files
+            .par_iter()
+            .filter_map(|f| {
+                let (score, indices) = matcher.fuzzy_indices(&f.relative, &query)?;
+                let name_score = matcher
+                    .fuzzy_match(&f.relative[f.name_start..], &query)
+                    .unwrap_or(0);
+                Some(FileMatch {
+                    score: score + name_score + recency(&f.path) - DEPTH_PENALTY * f.depth as i64,
+                    indices: utf16_indices(&f.relative, &indices),
+                    path: f.path.clone(),
+                    relative: f.relative.clone(),
+                })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.relative.cmp(&b.relative))
+        });
+        matches.truncate(limit);
+        matches
+    }
+
+    /// Remember that a file was opened, for ranking
+    pub fn record_opened(&self, path: &Path) {
+        let path = path.to_string_lossy().to_string();
+        let mut recent = self.recent.lock().unwrap();
+        recent.retain(|p| *p != path);
+        recent.insert(0, path);
+        recent.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Drop the index and watcher of a workspace
+    pub fn forget(&self, workspace_id: &str) {
+        self.workspaces.lock().unwrap().remove(workspace_id);
+    }
+}
@@ -0,0 +1,417 @@
+use crate::errors::{AppError, AppResult};
+use crate::models::FileVersion;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// SHA-256 of the given bytes as lowercase hex
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// SHA-256 of a file as lowercase hex, streamed so large media is not loaded at once
+pub fn file_hash(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Modification time in milliseconds since the Unix epoch
+pub fn modified_millis(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Current on-disk version of a file, used by the editor to detect external edits
+pub fn file_version(path: &Path) -> AppResult<FileVersion> {
+    let metadata = fs::metadata(path)?;
+    let bytes = fs::read(path)?;
+    Ok(version_of(&metadata, &bytes))
+}
+
+/// Version of a file whose full contents are already in memory
+pub fn version_of(metadata: &fs::Metadata, bytes: &[u8]) -> FileVersion {
+    FileVersion {
+        mtime: modified_millis(metadata),
+        size: metadata.len(),
+        hash: content_hash(bytes),
+    }
+}
+
+/// Fail with `AppError::Conflict` if the file changed since the editor loaded it
+pub fn check_unchanged(
+    path: &Path,
+    current: &[u8],
+    expected_mtime: Option<u64>,
+    expected_hash: Option<&str>,
+) -> AppResult<()> {
+    if let Some(expected) = expected_mtime {
+        let mtime = modified_millis(&fs::metadata(path)?);
+        if mtime != expected {
+            // Touching a file without editing it should not block the save
+            let same_content = expected_hash.is_some_and(|h| h == content_hash(current));
+            if !same_content {
+                return Err(AppError::Conflict(format!(
+                    "{} was modified on disk",
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    if let Some(expected) = expected_hash {
+        if content_hash(current) != expected {
+            return Err(AppError::Conflict(format!(
+                "{} was modified on disk",
+                path.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Decide whether a chunk of bytes looks like a binary file.
+///
+/// Files with a Unicode BOM are always text. Otherwise a NUL byte, or a high
+/// share of control characters, marks the data as binary, unless the NULs
+/// follow the every-other-byte pattern of BOM-less UTF-16.
+pub fn is_binary(sample: &[u8]) -> bool {
+    let sample = &sample[..sample.len().min(8192)];
+    if sample.is_empty()
+        || Encoding::for_bom(sample).is_some()
+        || utf16_without_bom(sample).is_some()
+    {
+        return false;
+    }
+    if sample.contains(&0) {
+        return true;
+    }
+
+    let control = sample
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\n' | b'\r' | b'\t' | 0x0C | 0x1B))
+        .count();
+    control * 10 > sample.len()
+}
+
+/// Detect UTF-16 without a BOM from the position of the zero bytes in ASCII text
+fn utf16_without_bom(sample: &[u8]) -> Option<&'static Encoding> {
+    if sample.len() < 4 {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let even_zeros = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_zeros = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+
+    if odd_zeros * 10 > pairs * 7 && even_zeros * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 10 > pairs * 7 && odd_zeros * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Detect the text encoding of `bytes`, returning it with the BOM length (0 if none)
+pub fn detect_encoding(bytes: &[u8]) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return (encoding, bom_len);
+    }
+    if let Some(encoding) = utf16_without_bom(&bytes[..bytes.len().min(8192)]) {
+        return (encoding, 0);
+    }
+
+    // An incomplete sequence at the very end is fine, ranged reads cut anywhere
+    match std::str::from_utf8(bytes) {
+        Ok(_) => return (UTF_8, 0),
+        Err(e) if e.error_len().is_none() => return (UTF_8, 0),
+        Err(_) => {}
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    (detector.guess(None, false), 0)
+}
+
+/// Decode `bytes` (without BOM) as `encoding`.
+///
+/// With `complete == false` a multi-byte sequence cut off at the end is
+/// dropped instead of being turned into a replacement character.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding, complete: bool) -> String {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::with_capacity(
+        decoder
+            .max_utf8_buffer_length(bytes.len())
+            .unwrap_or(bytes.len()),
+    );
+    let _ = decoder.decode_to_string(bytes, &mut text, complete);
+    text
+}
+
+/// Encode `text` as `encoding`, optionally prefixed with its BOM.
+///
+/// encoding_rs only decodes UTF-16, so that direction is done by hand.
+/// Fails if `encoding` cannot represent every character, instead of writing
+/// the `&#NNNN;` references encoding_rs substitutes for them.
+pub fn encode(text: &str, encoding: &'static Encoding, with_bom: bool) -> AppResult<Vec<u8>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let little_endian = encoding == UTF_16LE;
+        let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+        let units = with_bom
+            .then_some(0xFEFF)
+            .into_iter()
+            .chain(text.encode_utf16());
+        for unit in units {
+            bytes.extend_from_slice(&if little_endian {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            });
+        }
+        return Ok(bytes);
+    }
+
+    let (encoded, _, had_errors) = encoding.encode(text);
+    if had_errors {
+        let unmappable = text
+            .chars()
+            .find(|c| encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+            .unwrap_or_default();
+        return Err(AppError::NotSupported(format!(
+            "{} cannot represent {:?} (U+{:04X}), save the file as UTF-8 instead",
+            encoding.name(),
+            unmappable,
+            unmappable as u32
+        )));
+    }
+    let mut bytes = Vec::with_capacity(encoded.len() + UTF8_BOM.len());
+    if with_bom && encoding == UTF_8 {
+        bytes.extend_from_slice(UTF8_BOM);
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+/// Whether the first line break in `text` is CRLF
+pub fn uses_crlf(text: &str) -> bool {
+    text.find('\n')
+        .is_some_and(|i| i > 0 && text.as_bytes()[i - 1] == b'\r')
+}
+
+/// Keep the encoding, line endings and BOM the file already had.
+///
+/// The editor works with `\n`; if the existing file was CRLF the content is
+/// converted back. An explicit `encoding` label overrides the detected one.
+pub fn match_file_format(
+    existing: &[u8],
+    content: &str,
+    encoding: Option<&str>,
+) -> AppResult<Vec<u8>> {
+    let (detected, bom_len) = if is_binary(existing) {
+        (UTF_8, 0)
+    } else {
+        detect_encoding(existing)
+    };
+    let encoding = match encoding {
+        Some(label) => Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| AppError::NotSupported(format!("Unknown encoding: {}", label)))?,
+        None => detected,
+    };
+
+    let original = decode(
+        &existing[bom_len..existing.len().min(bom_len + 8192)],
+        detected,
+        false,
+    );
+    let content = if uses_crlf(&original) && !content.contains("\r\n") {
+        content.replace('\n', "\r\n")
+    } else {
+        content.to_string()
+    };
+
+    encode(&content, encoding, bom_len > 0)
+}
+
+/// Write `bytes` to `path` without ever leaving a half-written file behind.
+///
+/// The data goes to a temporary file in the same directory, is flushed to
+/// disk and then renamed over the target. Permissions of an existing file
+/// are carried over.
+pub fn atomic_write(path: &Path, bytes: &[u8]) -> AppResult<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| AppError::Internal(format!("No parent directory: {}", path.display())))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = dir.join(format!(".{}.{}.tmp", name, uuid::Uuid::new_v4()));
+
+    let result = (|| -> AppResult<()> {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+    use std::time::{Duration, SystemTime};
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn encodes_and_detects_round_trip() {
+        let text = "Grüße aus Köln, schöne Straße und ein Café am Rhein\n";
+        for (encoding, with_bom) in [
+            (UTF_8, false),
+            (UTF_8, true),
+            (UTF_16LE, true),
+            (UTF_16BE, true),
+            (UTF_16LE, false),
+            (UTF_16BE, false),
+            (WINDOWS_1252, false),
+        ] {
+            let bytes = encode(text, encoding, with_bom).unwrap();
+            let (detected, bom_len) = detect_encoding(&bytes);
+            assert_eq!(
+                (detected, bom_len > 0),
+                (encoding, with_bom),
+                "{}",
+                encoding.name()
+            );
+            assert_eq!(decode(&bytes[bom_len..], detected, true), text);
+        }
+
+        // A BOM already in the text is not written twice
+        let bytes = encode("\u{feff}a", UTF_8, true).unwrap();
+        assert_eq!(bytes, b"\xEF\xBB\xBFa");
+        assert!(matches!(
+            encode("a€", SHIFT_JIS, false),
+            Err(AppError::NotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn cut_off_utf8_is_still_utf8() {
+        let bytes = "naïve".as_bytes();
+        assert_eq!(detect_encoding(&bytes[..3]), (UTF_8, 0));
+        assert_eq!(decode(&bytes[..3], UTF_8, false), "na");
+    }
+
+    #[test]
+    fn keeps_line_endings_and_bom() {
+        let existing = b"\xEF\xBB\xBFone\r\ntwo\r\n";
+        assert_eq!(
+            match_file_format(existing, "one\nthree\n", None).unwrap(),
+            b"\xEF\xBB\xBFone\r\nthree\r\n"
+        );
+        assert!(matches!(
+            match_file_format(b"a", "a", Some("no-such-encoding")),
+            Err(AppError::NotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn atomic_write_replaces_contents_and_keeps_permissions() {
+        let dir = temp_dir("file-io-atomic");
+        let path = dir.join("a.txt");
+        atomic_write(&path, b"one").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"one");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o754)).unwrap();
+            atomic_write(&path, b"two").unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o754);
+        }
+        atomic_write(&path, b"three").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"three");
+
+        // No temporary files are left behind, also when the rename fails
+        assert!(atomic_write(&dir.join("missing").join("b.txt"), b"x").is_err());
+        fs::create_dir(dir.join("folder")).unwrap();
+        assert!(atomic_write(&dir.join("folder"), b"x").is_err());
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|f| f.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "folder"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn check_unchanged_detects_external_edits() {
+        let dir = temp_dir("file-io-unchanged");
+        let path = dir.join("a.txt");
+        fs::write(&path, b"one").unwrap();
+        let version = file_version(&path).unwrap();
+        let (mtime, hash) = (Some(version.mtime), Some(version.hash.as_str()));
+
+        check_unchanged(&path, b"one", None, None).unwrap();
+        check_unchanged(&path, b"one", mtime, hash).unwrap();
+        assert!(matches!(
+            check_unchanged(&path, b"two", None, hash),
+            Err(AppError::Conflict(_))
+        ));
+
+        // Touched without an edit: only a hash can tell
+        let file = fs::File::options().append(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        check_unchanged(&path, b"one", mtime, hash).unwrap();
+        assert!(matches!(
+            check_unchanged(&path, b"one", mtime, None),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            check_unchanged(&path, b"two", mtime, hash),
+            Err(AppError::Conflict(_))
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
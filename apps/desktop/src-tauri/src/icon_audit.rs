@@ -0,0 +1,524 @@
+//! Checks a Tauri project's icons against what the bundlers and stores expect.
+//!
+//! Looks at the `bundle.icon` list of `tauri.conf.json`, the icons folder
+//! and the generated mobile resources in `gen/android` and `gen/apple`.
+
+use crate::icons::{
+    self, ADAPTIVE_LAYER_DP, ANDROID_DENSITIES, DESKTOP_PNGS, ICNS_TYPES, ICO_SIZES,
+    WINDOWS_STORE_LOGOS,
+};
+use image::{ImageDecoder, ImageReader};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Where `tauri icon` looks for the source image, relative to the project root
+const SOURCE_CANDIDATES: &[&str] = &["app-icon.svg", "app-icon.png"];
+
+/// Smallest source that still gives a sharp 1024px icon
+const MIN_SOURCE_SIZE: u32 = 1024;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct IconIssue {
+    severity: String, // error or warning
+    code: String,
+    message: String,
+    path: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct IconReport {
+    icons_dir: Option<String>,
+    source: Option<String>, // image the icons were generated from, if found
+    checked: usize,         // number of icon files inspected
+    issues: Vec<IconIssue>,
+}
+
+struct Audit {
+    checked: usize,
+    issues: Vec<IconIssue>,
+}
+
+/// Dimensions and whether the pixel format has an alpha channel
+struct ImageInfo {
+    width: u32,
+    height: u32,
+    alpha: bool,
+}
+
+fn image_info(path: &Path) -> Option<ImageInfo> {
+    let decoder = ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_decoder()
+        .ok()?;
+    let (width, height) = decoder.dimensions();
+    Some(ImageInfo {
+        width,
+        height,
+        alpha: decoder.color_type().has_alpha(),
+    })
+}
+
+/// Pixel size encoded in a Tauri icon file name: `32x32.png` is 32,
+/// `128x128@2x.png` is 256
+fn size_from_name(name: &str) -> Option<u32> {
+    let stem = name.strip_suffix(".png")?;
+    let (dims, scale) = match stem.split_once('@') {
+        Some((dims, scale)) => (dims, scale.strip_suffix('x')?.parse::<u32>().ok()?),
+        None => (stem, 1),
+    };
+    let (w, h) = dims.split_once('x')?;
+    let (w, h) = (w.parse::<u32>().ok()?, h.parse::<u32>().ok()?);
+    (w == h).then_some(w * scale)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Audit {
+    fn report(&mut self, severity: &str, code: &str, message: String, path: &Path) {
+        self.issues.push(IconIssue {
+            severity: severity.to_string(),
+            code: code.to_string(),
+            message,
+            path: path.to_string_lossy().to_string(),
+        });
+    }
+
+    /// Check that a PNG exists and is `size` pixels square, optionally without alpha
+    fn check_png(&mut self, path: &Path, size: Option<u32>, alpha_allowed: bool) {
+        if !path.is_file() {
+            self.report(
+                "warning",
+                "missing-icon",
+                "Icon file is missing".to_string(),
+                path,
+            );
+            return;
+        }
+        self.checked += 1;
+        let Some(info) = image_info(path) else {
+            self.report(
+                "error",
+                "unreadable-icon",
+                "Not a readable image".to_string(),
+                path,
+            );
+            return;
+        };
+        if info.width != info.height {
+            self.report(
+                "error",
+                "not-square",
+                format!(
+                    "Icon is {}x{}, icons must be square",
+                    info.width, info.height
+                ),
+                path,
+            );
+        } else if let Some(size) = size.filter(|&s| s != info.width) {
+            self.report(
+                "error",
+                "wrong-size",
+                format!("Icon is {0}x{0}, expected {1}x{1}", info.width, size),
+                path,
+            );
+        }
+        if info.alpha && !alpha_allowed {
+            self.report(
+                "error",
+                "alpha-not-allowed",
+                "Icon has an alpha channel, which the App Store rejects".to_string(),
+                path,
+            );
+        }
+    }
+
+    fn check_ico(&mut self, path: &Path) {
+        self.checked += 1;
+        let dir = match fs::File::open(path).and_then(ico::IconDir::read) {
+            Ok(dir) => dir,
+            Err(e) => {
+                self.report(
+                    "error",
+                    "unreadable-icon",
+                    format!("Invalid ICO: {}", e),
+                    path,
+                );
+                return;
+            }
+        };
+        let missing: Vec<String> = ICO_SIZES
+            .iter()
+            .filter(|&&size| {
+                !dir.entries()
+                    .iter()
+                    .any(|e| e.width() == size && e.height() == size)
+            })
+            .map(|size| size.to_string())
+            .collect();
+        if !missing.is_empty() {
+            self.report(
+                "warning",
+                "missing-resolution",
+                format!("ICO has no {} px layers", missing.join(", ")),
+                path,
+            );
+        }
+    }
+
+    fn check_icns(&mut self, path: &Path) {
+        self.checked += 1;
+        let family = match fs::File::open(path).and_then(icns::IconFamily::read) {
+            Ok(family) => family,
+            Err(e) => {
+                self.report(
+                    "error",
+                    "unreadable-icon",
+                    format!("Invalid ICNS: {}", e),
+                    path,
+                );
+                return;
+            }
+        };
+        let available = family.available_icons();
+        let mut missing: Vec<String> = ICNS_TYPES
+            .iter()
+            .filter(|(_, wanted)| !available.contains(wanted))
+            .map(|(_, wanted)| {
+                let (size, density) = (wanted.screen_width(), wanted.pixel_density());
+                if density > 1 {
+                    format!("{0}x{0}@{1}x", size, density)
+                } else {
+                    format!("{0}x{0}", size)
+                }
+            })
+            .collect();
+        missing.dedup();
+        if !missing.is_empty() {
+            self.report(
+                "warning",
+                "missing-resolution",
+                format!("ICNS has no {} icons", missing.join(", ")),
+                path,
+            );
+        }
+    }
+
+    /// The icons listed in `tauri.conf.json`
+    fn check_bundle_config(&mut self, tauri_dir: &Path) -> Vec<PathBuf> {
+        let config_path = tauri_dir.join("tauri.conf.json");
+        let Ok(text) = fs::read_to_string(&config_path) else {
+            self.report(
+                "warning",
+                "missing-config",
+                "No tauri.conf.json next to the icons folder".to_string(),
+                &config_path,
+            );
+            return Vec::new();
+        };
+        let config: serde_json::Value = match serde_json::from_str(&text) {
+            Ok(config) => config,
+            Err(e) => {
+                self.report(
+                    "error",
+                    "invalid-config",
+                    format!("Cannot parse: {}", e),
+                    &config_path,
+                );
+                return Vec::new();
+            }
+        };
+
+        let listed: Vec<PathBuf> = config
+            .pointer("/bundle/icon")
+            .and_then(|v| v.as_array())
+            .map(|icons| {
+                icons
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(|icon| tauri_dir.join(icon))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if listed.is_empty() {
+            self.report(
+                "warning",
+                "no-bundle-icons",
+                "bundle.icon is empty, bundles get the default Tauri icon".to_string(),
+                &config_path,
+            );
+        }
+        for (ext, platform) in [("ico", "Windows"), ("icns", "macOS")] {
+            if !listed
+                .iter()
+                .any(|p| p.extension().is_some_and(|e| e == ext))
+            {
+                self.report(
+                    "warning",
+                    "missing-bundle-icon",
+                    format!(
+                        "bundle.icon lists no .{} file, needed for {} bundles",
+                        ext, platform
+                    ),
+                    &config_path,
+                );
+            }
+        }
+
+        for path in &listed {
+            if !path.is_file() {
+                self.report(
+                    "error",
+                    "missing-icon",
+                    "Listed in bundle.icon but does not exist".to_string(),
+                    path,
+                );
+                continue;
+            }
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("ico") => self.check_ico(path),
+                Some("icns") => self.check_icns(path),
+                _ => self.check_png(path, size_from_name(&name), true),
+            }
+        }
+        listed
+    }
+
+    fn check_icons_dir(&mut self, icons_dir: &Path, listed: &[PathBuf]) {
+        for &(name, size) in DESKTOP_PNGS.iter().chain(WINDOWS_STORE_LOGOS) {
+            let path = icons_dir.join(name);
+            // Listed icons were checked with the config already
+            if !listed.contains(&path) {
+                self.check_png(&path, Some(size), true);
+            }
+        }
+        for name in ["icon.ico", "icon.icns"] {
+            let path = icons_dir.join(name);
+            if listed.contains(&path) {
+                continue;
+            }
+            if !path.is_file() {
+                self.report(
+                    "warning",
+                    "missing-icon",
+                    "Icon file is missing".to_string(),
+                    &path,
+                );
+            } else if name.ends_with(".ico") {
+                self.check_ico(&path);
+            } else {
+                self.check_icns(&path);
+            }
+        }
+    }
+
+    fn check_android(&mut self, icons_dir: &Path) {
+        if !icons::project_dir(icons_dir)
+            .join("gen")
+            .join("android")
+            .is_dir()
+        {
+            return;
+        }
+        let res = icons::android_res_dir(icons_dir);
+        for &(density, size) in ANDROID_DENSITIES {
+            let mipmap = res.join(format!("mipmap-{}", density));
+            self.check_png(&mipmap.join("ic_launcher.png"), Some(size), true);
+            let round = mipmap.join("ic_launcher_round.png");
+            if round.is_file() {
+                self.check_png(&round, Some(size), true);
+            }
+            let layer = size * ADAPTIVE_LAYER_DP / 48;
+            for name in [
+                "ic_launcher_foreground.png",
+                "ic_launcher_monochrome.png",
+                "ic_launcher_background.png",
+            ] {
+                let path = mipmap.join(name);
+                if path.is_file() {
+                    self.check_png(&path, Some(layer), true);
+                }
+            }
+        }
+    }
+
+    fn check_apple(&mut self, icons_dir: &Path) {
+        if !icons::apple_dir(icons_dir).is_dir() {
+            return;
+        }
+        let icon_set = icons::app_icon_set_dir(icons_dir);
+        let contents_path = icon_set.join("Contents.json");
+        let contents: serde_json::Value = match fs::read_to_string(&contents_path) {
+            Ok(text) => match serde_json::from_str(&text) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    self.report(
+                        "error",
+                        "invalid-config",
+                        format!("Cannot parse: {}", e),
+                        &contents_path,
+                    );
+                    return;
+                }
+            },
+            Err(_) => {
+                self.report(
+                    "warning",
+                    "missing-config",
+                    "No AppIcon set, Xcode builds will have no icon".to_string(),
+                    &contents_path,
+                );
+                return;
+            }
+        };
+
+        let images = contents["images"].as_array().cloned().unwrap_or_default();
+        for image in &images {
+            let Some(name) = image["filename"].as_str() else {
+                continue;
+            };
+            // "83.5x83.5" points at "2x" scale is 167 pixels
+            let points = image["size"]
+                .as_str()
+                .and_then(|s| s.split('x').next())
+                .and_then(|w| w.parse::<f32>().ok());
+            let scale = image["scale"]
+                .as_str()
+                .and_then(|s| s.trim_end_matches('x').parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let size = points.map(|p| (p * scale).round() as u32);
+            // Dark icons are meant to be transparent
+            let alpha_allowed = image["appearances"]
+                .as_array()
+                .is_some_and(|a| a.iter().any(|v| v["value"] == "dark"));
+            self.check_png(&icon_set.join(name), size, alpha_allowed);
+        }
+    }
+
+    /// Flag generated icons that are older than the source image, one issue per folder
+    fn check_stale(&mut self, source: &Path, icons_dir: &Path) {
+        let Some(source_time) = modified(source) else {
+            return;
+        };
+        let folders = [
+            icons_dir.to_path_buf(),
+            icons::android_res_dir(icons_dir),
+            icons::app_icon_set_dir(icons_dir),
+        ];
+        for folder in folders {
+            let stale = walkdir::WalkDir::new(&folder)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .filter(|e| {
+                    e.path()
+                        .extension()
+                        .is_some_and(|ext| ext == "png" || ext == "ico" || ext == "icns")
+                })
+                .filter(|e| e.path() != source)
+                .filter(|e| modified(e.path()).is_some_and(|t| t < source_time))
+                .count();
+            if stale > 0 {
+                self.report(
+                    "warning",
+                    "stale-icons",
+                    format!(
+                        "{} icons are older than {}, regenerate them",
+                        stale,
+                        source.file_name().unwrap_or_default().to_string_lossy()
+                    ),
+                    &folder,
+                );
+            }
+        }
+    }
+
+    fn check_source(&mut self, source: &Path) {
+        let is_svg = source.extension().is_some_and(|e| e == "svg");
+        if is_svg {
+            return;
+        }
+        let Some(info) = image_info(source) else {
+            self.report(
+                "error",
+                "unreadable-icon",
+                "Not a readable image".to_string(),
+                source,
+            );
+            return;
+        };
+        if info.width != info.height {
+            self.report(
+                "error",
+                "not-square",
+                format!(
+                    "Source is {}x{}, icons will be letterboxed",
+                    info.width, info.height
+                ),
+                source,
+            );
+        }
+        if info.width.min(info.height) < MIN_SOURCE_SIZE {
+            self.report(
+                "warning",
+                "small-source",
+                format!(
+                    "Source is {}x{}, large icons will be upscaled from it",
+                    info.width, info.height
+                ),
+                source,
+            );
+        }
+    }
+}
+
+/// Audit the icons of the project at `project_root`. `icons_dir` is its
+/// icons folder, if there is one, and `source` the image icons are
+/// generated from, `app-icon.png` or `app-icon.svg` in the project root by
+/// default.
+pub fn audit(project_root: &Path, icons_dir: Option<&Path>, source: Option<&Path>) -> IconReport {
+    let mut audit = Audit {
+        checked: 0,
+        issues: Vec::new(),
+    };
+    let source = source.map(Path::to_path_buf).or_else(|| {
+        SOURCE_CANDIDATES
+            .iter()
+            .map(|name| project_root.join(name))
+            .find(|p| p.is_file())
+    });
+
+    if let Some(source) = &source {
+        audit.check_source(source);
+    }
+    match icons_dir {
+        Some(icons_dir) => {
+            let listed = audit.check_bundle_config(icons::project_dir(icons_dir));
+            audit.check_icons_dir(icons_dir, &listed);
+            audit.check_android(icons_dir);
+            audit.check_apple(icons_dir);
+            if let Some(source) = &source {
+                audit.check_stale(source, icons_dir);
+            }
+        }
+        None => audit.report(
+            "error",
+            "missing-icons-dir",
+            "No src-tauri/icons folder found".to_string(),
+            project_root,
+        ),
+    }
+
+    IconReport {
+        icons_dir: icons_dir.map(|d| d.to_string_lossy().to_string()),
+        source: source.map(|s| s.to_string_lossy().to_string()),
+        checked: audit.checked,
+        issues: audit.issues,
+    }
+}
@@ -0,0 +1,233 @@
+//! Icon masks: the platform shapes an icon is cut to, with padding and a
+//! drop shadow.
+//!
+//! Shapes are evaluated in a unit square (`-1..1` on both axes) and
+//! antialiased by supersampling, so every size comes out from the same
+//! geometry instead of a pixel grid tuned for one resolution.
+
+use crate::errors::{AppError, AppResult};
+use crate::icons::IconSource;
+use image::{imageops, Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Deserialize;
+
+/// Samples per pixel along each axis
+const SUPERSAMPLE: u32 = 4;
+
+/// Outline of a mask in the unit square
+#[derive(Clone, Copy, Debug)]
+pub enum Shape {
+    /// `|x|^n + |y|^n <= 1`, the continuous-corner "squircle"
+    Superellipse(f32),
+    /// Corner radii as a share of the half width: top left, top right,
+    /// bottom right, bottom left. All 0 is a square, all 1 a circle.
+    Rounded([f32; 4]),
+}
+
+impl Shape {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        match *self {
+            Shape::Superellipse(n) => x.abs().powf(n) + y.abs().powf(n) <= 1.0,
+            Shape::Rounded(radii) => {
+                let radius = match (x < 0.0, y < 0.0) {
+                    (true, true) => radii[0],
+                    (false, true) => radii[1],
+                    (false, false) => radii[2],
+                    (true, false) => radii[3],
+                };
+                // Distance into the corner square, zero along the straight edges
+                let dx = (x.abs() - (1.0 - radius)).max(0.0);
+                let dy = (y.abs() - (1.0 - radius)).max(0.0);
+                x.abs() <= 1.0 && y.abs() <= 1.0 && dx * dx + dy * dy <= radius * radius
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaskPreset {
+    Macos,
+    AndroidCircle,
+    AndroidSquircle,
+    AndroidTeardrop,
+    WindowsTile,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Shadow {
+    pub blur: f32,    // Gaussian sigma as a share of the icon size
+    pub offset: f32,  // downwards, as a share of the icon size
+    pub opacity: f32, // 0 to 1
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mask {
+    pub shape: Shape,
+    pub padding: f32, // on each side, as a share of the icon size
+    pub shadow: Option<Shadow>,
+}
+
+impl MaskPreset {
+    pub fn mask(self) -> Mask {
+        let (shape, padding, shadow) = match self {
+            // Big Sur grid: an 824px shape on the 1024px canvas with a soft shadow below
+            MaskPreset::Macos => (
+                Shape::Superellipse(5.0),
+                100.0 / 1024.0,
+                Some(Shadow {
+                    blur: 10.0 / 1024.0,
+                    offset: 10.0 / 1024.0,
+                    opacity: 0.3,
+                }),
+            ),
+            // Legacy launcher icons leave 2dp of the 48dp square free
+            MaskPreset::AndroidCircle => (Shape::Rounded([1.0; 4]), 2.0 / 48.0, None),
+            MaskPreset::AndroidSquircle => (Shape::Superellipse(4.0), 2.0 / 48.0, None),
+            MaskPreset::AndroidTeardrop => (Shape::Rounded([1.0, 1.0, 0.2, 1.0]), 2.0 / 48.0, None),
+            MaskPreset::WindowsTile => (Shape::Rounded([0.0; 4]), 0.0, None),
+        };
+        Mask {
+            shape,
+            padding,
+            shadow,
+        }
+    }
+}
+
+/// Mask settings as the frontend sends them: a preset with optional overrides
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct MaskOptions {
+    pub preset: MaskPreset,
+    pub padding: Option<f32>,  // replaces the preset padding, 0 to 0.4
+    pub shadow: Option<bool>,  // turns the preset shadow off, or a default one on
+    pub exponent: Option<f32>, // replaces the superellipse exponent, 2 (ellipse) and up
+}
+
+impl Default for MaskOptions {
+    fn default() -> Self {
+        Self {
+            preset: MaskPreset::Macos,
+            padding: None,
+            shadow: None,
+            exponent: None,
+        }
+    }
+}
+
+impl MaskOptions {
+    pub fn mask(&self) -> Mask {
+        let mut mask = self.preset.mask();
+        if let Some(padding) = self.padding {
+            mask.padding = padding.clamp(0.0, 0.4);
+        }
+        match self.shadow {
+            Some(false) => mask.shadow = None,
+            Some(true) if mask.shadow.is_none() => {
+                mask.shadow = MaskPreset::Macos.mask().shadow;
+            }
+            _ => {}
+        }
+        if let (Some(n), Shape::Superellipse(_)) = (self.exponent, mask.shape) {
+            mask.shape = Shape::Superellipse(n.max(2.0));
+        }
+        mask
+    }
+}
+
+/// The mask of each platform, applied to that platform's icons only.
+/// Platforms without one keep square icons.
+///
+/// iOS has no mask: the system cuts the AppIcon itself and expects a full
+/// square, pre-rounded corners would show the background under its shape.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlatformMasks {
+    pub macos: Option<Mask>,   // the ICNS
+    pub windows: Option<Mask>, // the ICO and the Store logos
+    pub android: Option<Mask>, // the legacy launcher icons
+}
+
+impl PlatformMasks {
+    /// Sort the masks the frontend sends by the platform of their preset
+    pub fn new(options: &[MaskOptions]) -> AppResult<Self> {
+        let mut masks = Self::default();
+        for option in options {
+            let slot = match option.preset {
+                MaskPreset::Macos => &mut masks.macos,
+                MaskPreset::AndroidCircle
+                | MaskPreset::AndroidSquircle
+                | MaskPreset::AndroidTeardrop => &mut masks.android,
+                MaskPreset::WindowsTile => &mut masks.windows,
+            };
+            if slot.replace(option.mask()).is_some() {
+                return Err(AppError::AlreadyExists(format!(
+                    "More than one mask for the platform of {:?}",
+                    option.preset
+                )));
+            }
+        }
+        Ok(masks)
+    }
+}
+
+/// Share of each pixel of a `size` square covered by `shape`
+fn coverage(shape: Shape, size: u32) -> Vec<f32> {
+    let samples = (SUPERSAMPLE * SUPERSAMPLE) as f32;
+    let to_unit = |i: u32, s: u32| {
+        ((i * SUPERSAMPLE + s) as f32 + 0.5) / (size * SUPERSAMPLE) as f32 * 2.0 - 1.0
+    };
+    (0..size * size)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % size, i / size);
+            let mut inside = 0;
+            for sy in 0..SUPERSAMPLE {
+                for sx in 0..SUPERSAMPLE {
+                    if shape.contains(to_unit(x, sx), to_unit(y, sy)) {
+                        inside += 1;
+                    }
+                }
+            }
+            inside as f32 / samples
+        })
+        .collect()
+}
+
+/// Render `source` at `size`, padded and cut to `mask`, with its shadow
+pub fn apply(source: &IconSource, size: u32, mask: &Mask) -> RgbaImage {
+    let content = ((size as f32 * (1.0 - 2.0 * mask.padding)).round() as u32).clamp(1, size);
+    let offset = (size - content) / 2;
+    let mut icon = source.render_padded(size, content);
+
+    let shape = coverage(mask.shape, content);
+    for (x, y, p) in icon.enumerate_pixels_mut() {
+        let inside = x >= offset && y >= offset && x < offset + content && y < offset + content;
+        let cover = if inside {
+            shape[((y - offset) * content + x - offset) as usize]
+        } else {
+            0.0
+        };
+        p[3] = (p[3] as f32 * cover).round() as u8;
+    }
+
+    let Some(shadow) = mask.shadow else {
+        return icon;
+    };
+    let mut silhouette = RgbaImage::new(size, size);
+    let shift = (shadow.offset * size as f32).round() as i64;
+    for (x, y, p) in icon.enumerate_pixels() {
+        if y as i64 + shift < size as i64 {
+            let alpha = (p[3] as f32 * shadow.opacity).round() as u8;
+            silhouette.put_pixel(x, (y as i64 + shift) as u32, Rgba([0, 0, 0, alpha]));
+        }
+    }
+    let sigma = shadow.blur * size as f32;
+    let mut out = if sigma >= 0.5 {
+        imageops::blur(&silhouette, sigma)
+    } else {
+        silhouette
+    };
+    imageops::overlay(&mut out, &icon, 0, 0);
+    out
+}
@@ -0,0 +1,950 @@
+//! Native icon generation.
+//!
+//! Produces the files `tauri icon` would (desktop PNGs, ICO, ICNS, Windows
+//! Store logos, the iOS AppIcon set and Android mipmaps) with the `image`,
+//! `ico` and `icns` crates, so no Node toolchain is needed. Output only
+//! depends on the source pixels: the same source always gives the same bytes.
+
+use crate::errors::{AppError, AppResult};
+use crate::file_io;
+use crate::icon_mask::{self, Mask, PlatformMasks};
+use crate::sandbox::WorkspaceRoots;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, Rgba, Rgba32FImage, RgbaImage};
+use rayon::prelude::*;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{self, ImageHrefResolver};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const DESKTOP_PNGS: &[(&str, u32)] = &[
+    ("32x32.png", 32),
+    ("64x64.png", 64),
+    ("128x128.png", 128),
+    ("128x128@2x.png", 256),
+    ("icon.png", 512),
+];
+
+pub const WINDOWS_STORE_LOGOS: &[(&str, u32)] = &[
+    ("Square30x30Logo.png", 30),
+    ("Square44x44Logo.png", 44),
+    ("Square71x71Logo.png", 71),
+    ("Square89x89Logo.png", 89),
+    ("Square107x107Logo.png", 107),
+    ("Square142x142Logo.png", 142),
+    ("Square150x150Logo.png", 150),
+    ("Square284x284Logo.png", 284),
+    ("Square310x310Logo.png", 310),
+    ("StoreLogo.png", 50),
+];
+
+pub const ICO_SIZES: &[u32] = &[16, 24, 32, 48, 64, 256];
+
+/// ICNS elements by pixel size, retina variants share the pixels of the
+/// next size up
+pub const ICNS_TYPES: &[(u32, icns::IconType)] = &[
+    (16, icns::IconType::RGB24_16x16),
+    (32, icns::IconType::RGB24_32x32),
+    (32, icns::IconType::RGBA32_16x16_2x),
+    (64, icns::IconType::RGBA32_32x32_2x),
+    (128, icns::IconType::RGB24_128x128),
+    (256, icns::IconType::RGBA32_256x256),
+    (256, icns::IconType::RGBA32_128x128_2x),
+    (512, icns::IconType::RGBA32_512x512),
+    (512, icns::IconType::RGBA32_256x256_2x),
+    (1024, icns::IconType::RGBA32_512x512_2x),
+];
+
+/// Entries of a classic all-sizes AppIcon set: file, idiom, size in points,
+/// scale and size in pixels. The names are the ones `tauri ios init` uses.
+const IOS_ICONS: &[(&str, &str, &str, u32, u32)] = &[
+    ("AppIcon-20x20@2x.png", "iphone", "20x20", 2, 40),
+    ("AppIcon-20x20@3x.png", "iphone", "20x20", 3, 60),
+    ("AppIcon-29x29@2x-1.png", "iphone", "29x29", 2, 58),
+    ("AppIcon-29x29@3x.png", "iphone", "29x29", 3, 87),
+    ("AppIcon-40x40@2x.png", "iphone", "40x40", 2, 80),
+    ("AppIcon-40x40@3x.png", "iphone", "40x40", 3, 120),
+    ("AppIcon-60x60@2x.png", "iphone", "60x60", 2, 120),
+    ("AppIcon-60x60@3x.png", "iphone", "60x60", 3, 180),
+    ("AppIcon-20x20@1x.png", "ipad", "20x20", 1, 20),
+    ("AppIcon-20x20@2x-1.png", "ipad", "20x20", 2, 40),
+    ("AppIcon-29x29@1x.png", "ipad", "29x29", 1, 29),
+    ("AppIcon-29x29@2x.png", "ipad", "29x29", 2, 58),
+    ("AppIcon-40x40@1x.png", "ipad", "40x40", 1, 40),
+    ("AppIcon-40x40@2x-1.png", "ipad", "40x40", 2, 80),
+    ("AppIcon-76x76@1x.png", "ipad", "76x76", 1, 76),
+    ("AppIcon-76x76@2x.png", "ipad", "76x76", 2, 152),
+    ("AppIcon-83.5x83.5@2x.png", "ipad", "83.5x83.5", 2, 167),
+    ("AppIcon-512@2x.png", "ios-marketing", "1024x1024", 1, 1024),
+];
+
+/// Single-size AppIcon set files, Xcode 14 and later scale these down itself
+const IOS_SINGLE_ICON: &str = "AppIcon-1024.png";
+const IOS_DARK_ICON: &str = "AppIcon-1024-dark.png";
+const IOS_TINTED_ICON: &str = "AppIcon-1024-tinted.png";
+
+/// Android densities with the legacy launcher size in pixels (48dp)
+pub const ANDROID_DENSITIES: &[(&str, u32)] = &[
+    ("mdpi", 48),
+    ("hdpi", 72),
+    ("xhdpi", 96),
+    ("xxhdpi", 144),
+    ("xxxhdpi", 192),
+];
+
+/// Adaptive icon layers are 108dp. Launcher masks show at most the centre
+/// 72dp, and the centre 66dp is never cut off.
+pub const ADAPTIVE_LAYER_DP: u32 = 108;
+const ADAPTIVE_VISIBLE_DP: u32 = 72;
+const ADAPTIVE_SAFE_ZONE_DP: u32 = 66;
+
+const ADAPTIVE_ICON_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<adaptive-icon xmlns:android="http://schemas.android.com/apk/res/android">
+    <background android:drawable="{background}" />
+    <foreground android:drawable="@mipmap/ic_launcher_foreground" />
+    <monochrome android:drawable="@mipmap/ic_launcher_monochrome" />
+</adaptive-icon>
+"#;
+
+const BACKGROUND_COLOR_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+    <color name="ic_launcher_background">{color}</color>
+</resources>
+"#;
+
+/// Adaptive icon background when none is given, as Android Studio uses
+static WHITE_BACKGROUND: Background = Background::Color(Rgba([255, 255, 255, 255]));
+
+/// Source image prepared for rendering at any icon size.
+///
+/// Raster pixels are kept as premultiplied `f32`, so transparent edges do
+/// not bleed dark fringes into downscaled icons. SVGs are rasterized anew
+/// for every size, so small icons stay crisp and the `shape-rendering`
+/// hints in the file are honoured.
+pub struct IconSource {
+    data: SourceData,
+}
+
+enum SourceData {
+    Raster(Rgba32FImage),
+    Vector(Box<resvg::usvg::Tree>),
+}
+
+impl IconSource {
+    pub fn new(image: &DynamicImage) -> Self {
+        let mut pixels = image.to_rgba32f();
+        for p in pixels.pixels_mut() {
+            let a = p[3];
+            p[0] *= a;
+            p[1] *= a;
+            p[2] *= a;
+        }
+        Self {
+            data: SourceData::Raster(pixels),
+        }
+    }
+
+    /// Parse an SVG (or gzipped SVGZ) document.
+    ///
+    /// Only embedded `data:` images are loaded, never files the document
+    /// points at. Text is refused rather than set in whatever fonts the
+    /// system has, so the same document renders the same everywhere.
+    pub fn from_svg(data: &[u8]) -> AppResult<Self> {
+        let invalid =
+            |e: &dyn std::fmt::Display| AppError::NotSupported(format!("Invalid SVG: {}", e));
+        let data = if data.starts_with(&[0x1f, 0x8b]) {
+            Cow::Owned(usvg::decompress_svgz(data).map_err(|e| invalid(&e))?)
+        } else {
+            Cow::Borrowed(data)
+        };
+        let text = std::str::from_utf8(&data).map_err(|e| invalid(&e))?;
+        let xml_options = usvg::roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        };
+        let document = usvg::roxmltree::Document::parse_with_options(text, xml_options)
+            .map_err(|e| invalid(&e))?;
+        if document.descendants().any(|n| n.has_tag_name("text")) {
+            return Err(AppError::NotSupported(
+                "SVG text is not supported, convert it to outlines (paths) first".to_string(),
+            ));
+        }
+
+        let options = usvg::Options {
+            resources_dir: None,
+            image_href_resolver: ImageHrefResolver {
+                resolve_data: ImageHrefResolver::default_data_resolver(),
+                resolve_string: Box::new(|_, _| None),
+            },
+            ..Default::default()
+        };
+        let tree = usvg::Tree::from_xmltree(&document, &options).map_err(|e| invalid(&e))?;
+        Ok(Self {
+            data: SourceData::Vector(Box::new(tree)),
+        })
+    }
+
+    /// Load a raster image or, judging by the extension, an SVG
+    pub fn open(path: &Path) -> AppResult<Self> {
+        let is_svg = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("svg") || e.eq_ignore_ascii_case("svgz"));
+        if is_svg {
+            return Self::from_svg(&fs::read(path)?);
+        }
+        let image = image::open(path)
+            .map_err(|e| AppError::Internal(format!("Failed to open image: {}", e)))?;
+        Ok(Self::new(&image))
+    }
+
+    /// Width and height in pixels, or in SVG user units
+    pub fn dimensions(&self) -> (f32, f32) {
+        match &self.data {
+            SourceData::Raster(pixels) => (pixels.width() as f32, pixels.height() as f32),
+            SourceData::Vector(tree) => (tree.size().width(), tree.size().height()),
+        }
+    }
+
+    /// Square icon of `size` pixels with the source fitted into its centre
+    pub fn render(&self, size: u32) -> RgbaImage {
+        self.render_padded(size, size)
+    }
+
+    /// Square canvas of `size` pixels with the source fitted into the
+    /// centred `content` square, keeping its aspect ratio
+    pub fn render_padded(&self, size: u32, content: u32) -> RgbaImage {
+        let (w, h) = self.dimensions();
+        self.render_scaled(size, content as f32 / w.max(h))
+    }
+
+    /// Square canvas of `size` pixels covered by the source, cropping the
+    /// longer side, for backgrounds
+    pub fn render_cover(&self, size: u32) -> RgbaImage {
+        let (w, h) = self.dimensions();
+        self.render_scaled(size, size as f32 / w.min(h))
+    }
+
+    /// The source scaled by `scale` and centred on a `size` square canvas
+    fn render_scaled(&self, size: u32, scale: f32) -> RgbaImage {
+        match &self.data {
+            SourceData::Raster(pixels) => render_raster(pixels, size, scale),
+            SourceData::Vector(tree) => render_vector(tree, size, scale),
+        }
+    }
+}
+
+fn render_raster(pixels: &Rgba32FImage, size: u32, scale: f32) -> RgbaImage {
+    let (w, h) = pixels.dimensions();
+    let width = ((w as f32 * scale).round() as u32).max(1);
+    let height = ((h as f32 * scale).round() as u32).max(1);
+    let resized = imageops::resize(pixels, width, height, FilterType::Lanczos3);
+
+    let mut canvas = RgbaImage::new(size, size);
+    let left = (size as i64 - width as i64) / 2;
+    let top = (size as i64 - height as i64) / 2;
+    for (x, y, p) in resized.enumerate_pixels() {
+        let (cx, cy) = (left + x as i64, top + y as i64);
+        if (0..size as i64).contains(&cx) && (0..size as i64).contains(&cy) {
+            canvas.put_pixel(cx as u32, cy as u32, unpremultiply(p));
+        }
+    }
+    canvas
+}
+
+fn render_vector(tree: &resvg::usvg::Tree, size: u32, scale: f32) -> RgbaImage {
+    let Some(mut pixmap) = Pixmap::new(size, size) else {
+        return RgbaImage::new(size, size);
+    };
+    let (w, h) = (tree.size().width() * scale, tree.size().height() * scale);
+    let transform = Transform::from_row(
+        scale,
+        0.0,
+        0.0,
+        scale,
+        (size as f32 - w) / 2.0,
+        (size as f32 - h) / 2.0,
+    );
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    let mut canvas = RgbaImage::new(size, size);
+    for (p, pixel) in canvas.pixels_mut().zip(pixmap.pixels()) {
+        let c = pixel.demultiply();
+        *p = Rgba([c.red(), c.green(), c.blue(), c.alpha()]);
+    }
+    canvas
+}
+
+fn unpremultiply(p: &Rgba<f32>) -> Rgba<u8> {
+    let a = p[3].clamp(0.0, 1.0);
+    let channel = |c: f32| {
+        let c = if a > 0.0 { c / a } else { 0.0 };
+        (c.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+    Rgba([
+        channel(p[0]),
+        channel(p[1]),
+        channel(p[2]),
+        (a * 255.0).round() as u8,
+    ])
+}
+
+/// Parse a CSS-style hex color: `#rgb`, `#rrggbb` or `#rrggbbaa`
+pub fn parse_color(color: &str) -> AppResult<Rgba<u8>> {
+    let invalid = || AppError::NotSupported(format!("Invalid color: {}", color));
+    let hex = color.trim().trim_start_matches('#');
+    if !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let hex: String = match hex.len() {
+        3 => hex
+            .chars()
+            .flat_map(|c| [c, c])
+            .chain("ff".chars())
+            .collect(),
+        6 => format!("{}ff", hex),
+        8 => hex.to_string(),
+        _ => return Err(invalid()),
+    };
+
+    let mut rgba = [0; 4];
+    for (i, channel) in rgba.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(Rgba(rgba))
+}
+
+/// `source` at `size`, cut to `mask` when there is one
+fn shaped(source: &IconSource, size: u32, mask: Option<&Mask>) -> RgbaImage {
+    match mask {
+        Some(mask) => icon_mask::apply(source, size, mask),
+        None => source.render(size),
+    }
+}
+
+/// Composite onto an opaque background
+pub fn flatten(image: &RgbaImage, background: Rgba<u8>) -> RgbaImage {
+    let mut out = RgbaImage::from_pixel(image.width(), image.height(), background);
+    imageops::overlay(&mut out, image, 0, 0);
+    out
+}
+
+/// Clear everything outside the inscribed circle, with a one pixel soft edge
+pub fn circle(image: &RgbaImage) -> RgbaImage {
+    let mut out = image.clone();
+    let radius = image.width() as f32 / 2.0;
+    for (x, y, p) in out.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - radius;
+        let dy = y as f32 + 0.5 - radius;
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        p[3] = (p[3] as f32 * coverage).round() as u8;
+    }
+    out
+}
+
+/// Android 13 themed icon layer: the shape of `image` in white, the launcher tints it
+pub fn monochrome(image: &RgbaImage) -> RgbaImage {
+    let mut out = image.clone();
+    for p in out.pixels_mut() {
+        *p = Rgba([255, 255, 255, p[3]]);
+    }
+    out
+}
+
+/// iOS 18 tinted icon: the luminance of `image` over black, the system
+/// colors it with the tint the user picked
+pub fn tinted(image: &RgbaImage) -> RgbaImage {
+    let mut out = image.clone();
+    for p in out.pixels_mut() {
+        let luma = 0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32;
+        let gray = (luma * p[3] as f32 / 255.0).round() as u8;
+        *p = Rgba([gray, gray, gray, 255]);
+    }
+    out
+}
+
+/// PNG with fixed encoder settings and no metadata chunks
+pub fn encode_png(image: &RgbaImage) -> AppResult<Vec<u8>> {
+    let opaque = image.pixels().all(|p| p[3] == 255);
+    let mut bytes = Vec::new();
+    let encoder =
+        PngEncoder::new_with_quality(&mut bytes, CompressionType::Best, PngFilter::Adaptive);
+    let result = if opaque {
+        let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+        encoder.write_image(&rgb, image.width(), image.height(), ExtendedColorType::Rgb8)
+    } else {
+        encoder.write_image(
+            image,
+            image.width(),
+            image.height(),
+            ExtendedColorType::Rgba8,
+        )
+    };
+    result.map_err(|e| AppError::Internal(format!("Failed to encode PNG: {}", e)))?;
+    Ok(bytes)
+}
+
+pub fn encode_ico(images: &[RgbaImage]) -> AppResult<Vec<u8>> {
+    let mut icon_dir = ico::IconDir::new(ico::ResourceType::Icon);
+    for image in images {
+        let icon = ico::IconImage::from_rgba_data(image.width(), image.height(), image.to_vec());
+        icon_dir.add_entry(
+            ico::IconDirEntry::encode(&icon).map_err(|e| AppError::Internal(e.to_string()))?,
+        );
+    }
+    let mut bytes = Vec::new();
+    icon_dir.write(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// ICNS from images keyed by pixel size, see `ICNS_TYPES`
+pub fn encode_icns(image_for: impl Fn(u32) -> RgbaImage) -> AppResult<Vec<u8>> {
+    let mut family = icns::IconFamily::new();
+    for &(size, icon_type) in ICNS_TYPES {
+        let image = icns::Image::from_data(
+            icns::PixelFormat::RGBA,
+            size,
+            size,
+            image_for(size).into_raw(),
+        )?;
+        family.add_icon_with_type(&image, icon_type)?;
+    }
+    let mut bytes = Vec::new();
+    family.write(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Background layer of an Android adaptive icon
+pub enum Background {
+    Color(Rgba<u8>),
+    Image(IconSource),
+}
+
+impl Background {
+    fn render(&self, size: u32) -> RgbaImage {
+        match self {
+            Background::Color(color) => RgbaImage::from_pixel(size, size, *color),
+            Background::Image(source) => source.render_cover(size),
+        }
+    }
+}
+
+/// Layers of the Android adaptive icon
+pub struct AndroidOptions {
+    pub foreground: Option<IconSource>, // the main source when not set
+    pub background: Option<Background>, // white when not set
+}
+
+/// How the iOS AppIcon set is made
+pub struct IosOptions {
+    pub background: Rgba<u8>, // App Store icons may not have alpha, so it is flattened onto this
+    pub single_size: bool,    // one 1024px icon instead of every size
+    pub dark: Option<IconSource>, // dark appearance, kept transparent; single size only
+    pub tinted: bool,         // tinted appearance; single size only
+}
+
+/// Which icon sets to generate
+pub struct IconOptions {
+    pub desktop: bool,
+    pub ios: Option<IosOptions>,
+    pub android: Option<AndroidOptions>,
+    pub masks: PlatformMasks,
+}
+
+/// A PNG to write and how to render it
+type Job<'a> = (PathBuf, Box<dyn Fn() -> RgbaImage + Send + Sync + 'a>);
+
+/// The folder holding `icons/` and `gen/`, usually `src-tauri`
+pub fn project_dir(icons_dir: &Path) -> &Path {
+    if icons_dir.file_name().is_some_and(|n| n == "icons") {
+        icons_dir.parent().unwrap_or(icons_dir)
+    } else {
+        icons_dir
+    }
+}
+
+/// Android resources go into the generated Gradle project when there is one
+pub fn android_res_dir(icons_dir: &Path) -> PathBuf {
+    let android = project_dir(icons_dir).join("gen").join("android");
+    let gradle_res = android.join("app").join("src").join("main").join("res");
+    if gradle_res.is_dir() {
+        gradle_res
+    } else {
+        android
+    }
+}
+
+pub fn apple_dir(icons_dir: &Path) -> PathBuf {
+    project_dir(icons_dir).join("gen").join("apple")
+}
+
+/// The asset catalog Xcode builds the app icon from
+pub fn app_icon_set_dir(icons_dir: &Path) -> PathBuf {
+    apple_dir(icons_dir)
+        .join("Assets.xcassets")
+        .join("AppIcon.appiconset")
+}
+
+/// The folders generated files go into, each resolved through the workspace
+/// roots since `gen/` is next to the icons folder rather than inside it
+pub struct OutputDirs {
+    pub icons: PathBuf,
+    pub android_res: PathBuf,
+    pub apple: PathBuf,
+}
+
+impl OutputDirs {
+    pub fn resolve(roots: &WorkspaceRoots, icons_dir: &Path) -> AppResult<Self> {
+        let resolve = |dir: PathBuf| roots.resolve(&dir.to_string_lossy());
+        let icons = resolve(icons_dir.to_path_buf())?;
+        Ok(Self {
+            android_res: resolve(android_res_dir(&icons))?,
+            apple: resolve(apple_dir(&icons))?,
+            icons,
+        })
+    }
+
+    pub fn app_icon_set(&self) -> PathBuf {
+        self.apple
+            .join("Assets.xcassets")
+            .join("AppIcon.appiconset")
+    }
+
+    /// Fail unless `dir` lies below one of the output folders without
+    /// passing a symlink, which may lead out of the workspace. Missing
+    /// folders are created when `create` is set, each only after the folder
+    /// above it passed.
+    fn check_dir(&self, dir: &Path, create: bool) -> AppResult<()> {
+        let denied = || {
+            AppError::PermissionDenied(format!("{} is outside the output folders", dir.display()))
+        };
+        let base = [&self.icons, &self.android_res, &self.apple]
+            .into_iter()
+            .find(|d| dir.starts_with(d))
+            .ok_or_else(denied)?;
+        if create {
+            fs::create_dir_all(base)?;
+        }
+        let mut current = base.clone();
+        for component in dir.strip_prefix(base).unwrap_or(dir).components() {
+            let Component::Normal(name) = component else {
+                return Err(denied());
+            };
+            current.push(name);
+            match fs::symlink_metadata(&current) {
+                Ok(metadata) if metadata.is_dir() => {}
+                Ok(_) => return Err(denied()),
+                Err(_) if create => fs::create_dir(&current)?,
+                Err(_) => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a generated file, creating its folder. A symlink in place of
+    /// the file is refused rather than followed.
+    pub fn write(&self, path: &Path, bytes: &[u8]) -> AppResult<()> {
+        let parent = path.parent().unwrap_or(path);
+        self.check_dir(parent, true)?;
+        if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+            return Err(AppError::PermissionDenied(format!(
+                "{} is a symlink, not overwriting it",
+                path.display()
+            )));
+        }
+        write(path, bytes)
+    }
+}
+
+/// Write a generated file, creating its folder
+pub fn write(path: &Path, bytes: &[u8]) -> AppResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    file_io::atomic_write(path, bytes)
+}
+
+/// Legacy and adaptive launcher icons for every density.
+///
+/// Without a separate foreground or background the legacy icons are the
+/// plain source, as `tauri icon` makes them. Otherwise they are the
+/// composed layers, cropped to the part a launcher mask would show. `mask`
+/// cuts `ic_launcher.png`, the round icon is always a circle.
+fn android_jobs<'a>(
+    source: &'a IconSource,
+    android: &'a AndroidOptions,
+    mask: Option<&'a Mask>,
+    res: &Path,
+    jobs: &mut Vec<Job<'a>>,
+    files: &mut Vec<(PathBuf, Vec<u8>)>,
+) {
+    let foreground = android.foreground.as_ref().unwrap_or(source);
+    let layered = android.foreground.is_some() || android.background.is_some();
+    let background = android.background.as_ref().unwrap_or(&WHITE_BACKGROUND);
+    let render_foreground = move |layer: u32| {
+        foreground.render_padded(layer, layer * ADAPTIVE_SAFE_ZONE_DP / ADAPTIVE_LAYER_DP)
+    };
+    let legacy = move |size: u32| {
+        if !layered {
+            return source.render(size);
+        }
+        let layer = size * ADAPTIVE_LAYER_DP / ADAPTIVE_VISIBLE_DP;
+        let mut composed = background.render(layer);
+        imageops::overlay(&mut composed, &render_foreground(layer), 0, 0);
+        let offset = (layer - size) / 2;
+        imageops::crop_imm(&composed, offset, offset, size, size).to_image()
+    };
+    let shaped_legacy = move |size: u32| match mask {
+        Some(mask) if layered => {
+            let composed = IconSource::new(&DynamicImage::ImageRgba8(legacy(size)));
+            icon_mask::apply(&composed, size, mask)
+        }
+        _ => shaped(source, size, mask),
+    };
+
+    for &(density, size) in ANDROID_DENSITIES {
+        let mipmap = res.join(format!("mipmap-{}", density));
+        let layer = size * ADAPTIVE_LAYER_DP / 48;
+        jobs.push((
+            mipmap.join("ic_launcher.png"),
+            Box::new(move || shaped_legacy(size)),
+        ));
+        jobs.push((
+            mipmap.join("ic_launcher_round.png"),
+            Box::new(move || circle(&legacy(size))),
+        ));
+        jobs.push((
+            mipmap.join("ic_launcher_foreground.png"),
+            Box::new(move || render_foreground(layer)),
+        ));
+        jobs.push((
+            mipmap.join("ic_launcher_monochrome.png"),
+            Box::new(move || monochrome(&render_foreground(layer))),
+        ));
+        if let Some(Background::Image(image)) = &android.background {
+            jobs.push((
+                mipmap.join("ic_launcher_background.png"),
+                Box::new(move || image.render_cover(layer)),
+            ));
+        }
+    }
+
+    let background_ref = match background {
+        Background::Image(_) => "@mipmap/ic_launcher_background".to_string(),
+        Background::Color(color) => {
+            let hex = format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2]);
+            let xml = BACKGROUND_COLOR_XML.replace("{color}", &hex);
+            files.push((
+                res.join("values").join("ic_launcher_background.xml"),
+                xml.into_bytes(),
+            ));
+            "@color/ic_launcher_background".to_string()
+        }
+    };
+    let adaptive = ADAPTIVE_ICON_XML.replace("{background}", &background_ref);
+    let anydpi = res.join("mipmap-anydpi-v26");
+    for name in ["ic_launcher.xml", "ic_launcher_round.xml"] {
+        files.push((anydpi.join(name), adaptive.clone().into_bytes()));
+    }
+}
+
+/// The images of an AppIcon set and its `Contents.json`
+fn ios_jobs<'a>(
+    source: &'a IconSource,
+    ios: &'a IosOptions,
+    icon_set: &Path,
+    jobs: &mut Vec<Job<'a>>,
+    files: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> AppResult<()> {
+    let background = ios.background;
+    let images: Vec<serde_json::Value> = if ios.single_size {
+        let appearance =
+            |value: &str| serde_json::json!([{ "appearance": "luminosity", "value": value }]);
+        let mut images = vec![serde_json::json!({
+            "filename": IOS_SINGLE_ICON,
+            "idiom": "universal",
+            "platform": "ios",
+            "size": "1024x1024",
+        })];
+        jobs.push((
+            icon_set.join(IOS_SINGLE_ICON),
+            Box::new(move || flatten(&source.render(1024), background)),
+        ));
+        if let Some(dark) = &ios.dark {
+            images.push(serde_json::json!({
+                "appearances": appearance("dark"),
+                "filename": IOS_DARK_ICON,
+                "idiom": "universal",
+                "platform": "ios",
+                "size": "1024x1024",
+            }));
+            jobs.push((
+                icon_set.join(IOS_DARK_ICON),
+                Box::new(move || dark.render(1024)),
+            ));
+        }
+        if ios.tinted {
+            images.push(serde_json::json!({
+                "appearances": appearance("tinted"),
+                "filename": IOS_TINTED_ICON,
+                "idiom": "universal",
+                "platform": "ios",
+                "size": "1024x1024",
+            }));
+            jobs.push((
+                icon_set.join(IOS_TINTED_ICON),
+                Box::new(move || tinted(&source.render(1024))),
+            ));
+        }
+        images
+    } else {
+        if ios.dark.is_some() || ios.tinted {
+            return Err(AppError::NotSupported(
+                "Dark and tinted iOS icons need the single-size AppIcon set".to_string(),
+            ));
+        }
+        IOS_ICONS
+            .iter()
+            .map(|&(name, idiom, points, scale, size)| {
+                jobs.push((
+                    icon_set.join(name),
+                    Box::new(move || flatten(&source.render(size), background)),
+                ));
+                serde_json::json!({
+                    "filename": name,
+                    "idiom": idiom,
+                    "scale": format!("{}x", scale),
+                    "size": points,
+                })
+            })
+            .collect()
+    };
+
+    let contents = serde_json::json!({
+        "images": images,
+        "info": { "author": "xcode", "version": 1 },
+    });
+    let json =
+        serde_json::to_vec_pretty(&contents).map_err(|e| AppError::Internal(e.to_string()))?;
+    files.push((icon_set.join("Contents.json"), json));
+    Ok(())
+}
+
+/// Delete app icons left over from the other AppIcon set layout, Xcode warns
+/// about images no entry refers to
+fn remove_stale_icons(
+    dirs: &OutputDirs,
+    icon_set: &Path,
+    files: &[(PathBuf, Vec<u8>)],
+    jobs: &[Job],
+) -> AppResult<()> {
+    dirs.check_dir(icon_set, false)?;
+    let Ok(entries) = fs::read_dir(icon_set) else {
+        return Ok(());
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_ok_and(|t| t.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let written = files
+            .iter()
+            .map(|(p, _)| p)
+            .chain(jobs.iter().map(|(p, _)| p))
+            .any(|p| *p == path);
+        if name.starts_with("AppIcon") && name.ends_with(".png") && !written {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Render the selected icon sets into the icons folder and the `gen/` folder
+/// next to it, returning the files written.
+pub fn generate(
+    source: &IconSource,
+    dirs: &OutputDirs,
+    options: &IconOptions,
+) -> AppResult<Vec<PathBuf>> {
+    let icons_dir = &dirs.icons;
+    let mut jobs: Vec<Job> = Vec::new();
+    let mut files: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+
+    let masks = &options.masks;
+    if options.desktop {
+        for &(name, size) in DESKTOP_PNGS {
+            jobs.push((icons_dir.join(name), Box::new(move || source.render(size))));
+        }
+        let windows = masks.windows.as_ref();
+        for &(name, size) in WINDOWS_STORE_LOGOS {
+            jobs.push((
+                icons_dir.join(name),
+                Box::new(move || shaped(source, size, windows)),
+            ));
+        }
+
+        let ico_images: Vec<RgbaImage> = ICO_SIZES
+            .par_iter()
+            .map(|&size| shaped(source, size, windows))
+            .collect();
+        files.push((icons_dir.join("icon.ico"), encode_ico(&ico_images)?));
+
+        let icns = encode_icns(|size| shaped(source, size, masks.macos.as_ref()))?;
+        files.push((icons_dir.join("icon.icns"), icns));
+    }
+
+    if let Some(ios) = &options.ios {
+        let icon_set = dirs.app_icon_set();
+        ios_jobs(source, ios, &icon_set, &mut jobs, &mut files)?;
+        remove_stale_icons(dirs, &icon_set, &files, &jobs)?;
+    }
+
+    if let Some(android) = &options.android {
+        let mask = masks.android.as_ref();
+        android_jobs(
+            source,
+            android,
+            mask,
+            &dirs.android_res,
+            &mut jobs,
+            &mut files,
+        );
+    }
+
+    let rendered: Vec<(PathBuf, Vec<u8>)> = jobs
+        .into_par_iter()
+        .map(|(path, render)| Ok((path, encode_png(&render())?)))
+        .collect::<AppResult<_>>()?;
+    files.extend(rendered);
+
+    for (path, bytes) in &files {
+        dirs.write(path, bytes)?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icon_mask::MaskPreset;
+
+    fn alpha_at_corner(path: &Path) -> u8 {
+        image::open(path).unwrap().to_rgba8().get_pixel(0, 0)[3]
+    }
+
+    #[test]
+    fn svg_text_is_refused() {
+        let square = br##"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="8">
+            <rect width="8" height="8" fill="#f00"/></svg>"##;
+        let source = IconSource::from_svg(square).unwrap();
+        assert_eq!(source.render(4).get_pixel(2, 2), &Rgba([255, 0, 0, 255]));
+
+        let text = br#"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="8">
+            <text x="0" y="8">A</text></svg>"#;
+        assert!(matches!(
+            IconSource::from_svg(text),
+            Err(AppError::NotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn each_platform_gets_its_own_mask() {
+        let base = std::env::temp_dir().join(format!("icon-masks-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        let base = base.canonicalize().unwrap();
+        let dirs = OutputDirs {
+            icons: base.join("icons"),
+            android_res: base.join("gen").join("android"),
+            apple: base.join("gen").join("apple"),
+        };
+        let options = IconOptions {
+            desktop: true,
+            ios: Some(IosOptions {
+                background: Rgba([255, 255, 255, 255]),
+                single_size: true,
+                dark: None,
+                tinted: false,
+            }),
+            android: Some(AndroidOptions {
+                foreground: None,
+                background: None,
+            }),
+            masks: PlatformMasks {
+                macos: Some(MaskPreset::Macos.mask()),
+                android: Some(MaskPreset::AndroidCircle.mask()),
+                windows: None,
+            },
+        };
+        let red = RgbaImage::from_pixel(64, 64, Rgba([255, 0, 0, 255]));
+        let source = IconSource::new(&DynamicImage::ImageRgba8(red));
+        generate(&source, &dirs, &options).unwrap();
+
+        // Square platforms keep the opaque corners of the source
+        assert_eq!(alpha_at_corner(&dirs.icons.join("32x32.png")), 255);
+        assert_eq!(
+            alpha_at_corner(&dirs.icons.join("Square44x44Logo.png")),
+            255
+        );
+
+        let icns = fs::File::open(dirs.icons.join("icon.icns")).unwrap();
+        let family = icns::IconFamily::read(icns).unwrap();
+        let icon = family
+            .get_icon_with_type(icns::IconType::RGBA32_512x512)
+            .unwrap();
+        assert_eq!(icon.data()[3], 0);
+
+        let launcher = dirs.android_res.join("mipmap-mdpi").join("ic_launcher.png");
+        assert_eq!(alpha_at_corner(&launcher), 0);
+
+        // iOS cuts the AppIcon itself, it stays a full square
+        let app_icon = image::open(dirs.app_icon_set().join(IOS_SINGLE_ICON)).unwrap();
+        assert_eq!(app_icon.to_rgba8().get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_dirs_refuse_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("icon-links-{}", std::process::id()));
+        let outside = base.join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        let base = base.canonicalize().unwrap();
+        let outside = outside.canonicalize().unwrap();
+        let dirs = OutputDirs {
+            icons: base.join("icons"),
+            android_res: base.join("res"),
+            apple: base.join("apple"),
+        };
+
+        // A planted link in place of a generated file
+        fs::create_dir_all(&dirs.icons).unwrap();
+        let target = outside.join("bashrc");
+        fs::write(&target, b"keep").unwrap();
+        symlink(&target, dirs.icons.join("icon.png")).unwrap();
+        assert!(dirs.write(&dirs.icons.join("icon.png"), b"icon").is_err());
+        assert_eq!(fs::read(&target).unwrap(), b"keep");
+
+        // A planted link in place of a folder, nothing is created behind it
+        fs::create_dir_all(&dirs.android_res).unwrap();
+        symlink(&outside, dirs.android_res.join("mipmap-hdpi")).unwrap();
+        let nested = dirs.android_res.join("mipmap-hdpi").join("a").join("b.png");
+        assert!(dirs.write(&nested, b"icon").is_err());
+        assert!(!outside.join("a").exists());
+
+        assert!(dirs.write(&base.join("elsewhere.png"), b"icon").is_err());
+        dirs.write(&dirs.apple.join("x").join("y.png"), b"icon")
+            .unwrap();
+        assert_eq!(
+            fs::read(dirs.apple.join("x").join("y.png")).unwrap(),
+            b"icon"
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
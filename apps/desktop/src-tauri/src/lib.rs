@@ -1,6 +1,22 @@
+pub mod android_xml;
+pub mod archive;
+pub mod asset_inventory;
+pub mod asset_optimizer;
+pub mod command_refs;
 pub mod commands;
 pub mod errors;
+pub mod file_index;
+pub mod file_io;
+pub mod icon_audit;
+pub mod icon_mask;
+pub mod icons;
 pub mod models;
+pub mod sandbox;
+pub mod splash;
+pub mod symbols;
+pub mod thumbnails;
+pub mod trigram_index;
+pub mod workspace;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -8,17 +24,47 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(sandbox::WorkspaceRoots::default())
+        .manage(workspace::WorkspaceManager::default())
+        .manage(commands::search::SearchRegistry::default())
+        .manage(file_index::FileIndex::default())
+        .manage(symbols::SymbolIndex::default())
+        .manage(trigram_index::SearchIndexes::default())
+        .manage(thumbnails::ThumbnailCache::default())
         .invoke_handler(tauri::generate_handler![
             commands::env_check::check_environment,
             commands::project_init::scan_project,
             commands::project_init::read_file,
+            commands::project_init::read_file_ex,
             commands::project_init::write_file,
+            commands::project_init::stat_file,
+            commands::file_ops::create_file,
+            commands::file_ops::create_dir,
+            commands::file_ops::delete_path,
+            commands::file_ops::rename_path,
+            commands::file_ops::copy_path,
+            commands::file_ops::move_path,
             commands::runner::run_command,
             commands::assets::get_assets,
             commands::assets::generate_icons,
+            commands::assets::generate_splash,
+            commands::assets::preview_icon_mask,
+            commands::assets::validate_icons,
+            commands::assets::optimize_assets,
+            commands::assets::get_thumbnail,
             commands::assets::find_icons_dir,
             commands::mobile::get_devices,
             commands::search::search_in_files,
+            commands::search::start_search,
+            commands::search::cancel_search,
+            commands::search::find_files,
+            commands::search::rebuild_search_index,
+            commands::search::delete_search_index,
+            commands::symbols::search_symbols,
+            commands::symbols::document_symbols,
+            commands::symbols::analyze_commands,
+            commands::replace::replace_in_files,
+            commands::replace::undo_replace,
             commands::git::git_status,
             commands::git::git_stage,
             commands::git::git_unstage,
@@ -30,6 +76,14 @@ pub fn run() {
             commands::git::git_branch,
             commands::config::save_config,
             commands::config::load_config,
+            commands::workspace::pick_workspace_folder,
+            commands::workspace::open_workspace,
+            commands::workspace::close_workspace,
+            commands::workspace::list_workspaces,
+            commands::workspace::add_workspace_root,
+            commands::workspace::remove_workspace_root,
+            commands::workspace::update_workspace_settings,
+            commands::workspace::get_recent_projects,
             commands::quick_commands::detect_package_manager,
             commands::quick_commands::detect_project_info,
             commands::quick_commands::read_android_version,
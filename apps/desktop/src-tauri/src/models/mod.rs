@@ -9,6 +9,36 @@ pub struct FileNode {
     pub size: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileVersion {
+    pub mtime: u64, // milliseconds since epoch
+    pub size: u64,
+    pub hash: String, // sha256 hex
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileContent {
+    pub content: String,
+    pub encoding: String, // WHATWG label, e.g. "UTF-8", "windows-1252", "UTF-16LE"
+    pub has_bom: bool,
+    pub line_ending: String, // "lf" | "crlf"
+    pub is_binary: bool,
+    pub size: u64,
+    pub offset: u64,
+    pub bytes_read: u64,
+    pub truncated: bool,
+    pub version: Option<FileVersion>, // only set when the whole file was read
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    pub roots: Vec<String>, // canonical paths, the first one is the primary root
+    #[serde(default)]
+    pub settings: serde_json::Value,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CheckResult {
     pub name: String,
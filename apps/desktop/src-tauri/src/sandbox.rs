@@ -0,0 +1,250 @@
+use crate::errors::{AppError, AppResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Workspace roots the webview is allowed to touch.
+///
+/// Every filesystem command resolves its input through this guard, so a path
+/// coming from the frontend can never reach outside the folders the user
+/// explicitly opened, whether through `..`, an absolute path or a symlink.
+///
+/// The webview cannot approve a root by itself: a folder only becomes one
+/// after the user picked it in the backend folder dialog, or when it is
+/// already in the persisted workspaces.
+///
+/// This confines the file commands only. The console (`run_command`) runs
+/// whatever program it is given inside a workspace folder, so the guard is
+/// not a boundary against a compromised webview, just against stray paths.
+#[derive(Default)]
+pub struct WorkspaceRoots {
+    roots: RwLock<Vec<PathBuf>>,
+    picked: RwLock<Vec<PathBuf>>,
+}
+
+impl WorkspaceRoots {
+    /// Approve a checked root, see [`workspace_root`]
+    pub fn add(&self, root: PathBuf) {
+        let mut roots = self.roots.write().unwrap();
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    /// Remember a folder the user picked in the folder dialog, so the next
+    /// request to open it is trusted
+    pub fn remember_pick(&self, root: PathBuf) {
+        self.picked.write().unwrap().push(root);
+    }
+
+    /// Whether `root` was picked by the user, consuming the pick
+    pub fn take_pick(&self, root: &Path) -> bool {
+        let mut picked = self.picked.write().unwrap();
+        let before = picked.len();
+        picked.retain(|p| p != root);
+        picked.len() != before
+    }
+
+    pub fn remove(&self, path: &str) {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        self.roots.write().unwrap().retain(|r| *r != canonical);
+    }
+
+    pub fn list(&self) -> Vec<PathBuf> {
+        self.roots.read().unwrap().clone()
+    }
+
+    /// Resolve a path the command is going to read or write through.
+    ///
+    /// Symlinks are followed all the way, so a link inside the workspace that
+    /// points at `~/.ssh` is rejected. The path does not have to exist yet.
+    pub fn resolve(&self, path: &str) -> AppResult<PathBuf> {
+        let resolved = canonicalize_lenient(Path::new(path), true)?;
+        self.check(path, resolved)
+    }
+
+    /// Resolve a path the command is going to create, rename or delete.
+    ///
+    /// The last component is not followed, so operations on a symlink act on
+    /// the link itself rather than its target.
+    pub fn resolve_entry(&self, path: &str) -> AppResult<PathBuf> {
+        let resolved = canonicalize_lenient(Path::new(path), false)?;
+        self.check(path, resolved)
+    }
+
+    /// Whether `path` is exactly one of the workspace roots
+    pub fn is_root(&self, path: &Path) -> bool {
+        self.roots.read().unwrap().iter().any(|r| r == path)
+    }
+
+    fn check(&self, requested: &str, resolved: PathBuf) -> AppResult<PathBuf> {
+        let roots = self.roots.read().unwrap();
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(resolved)
+        } else {
+            log::warn!("Blocked access outside workspace: {}", requested);
+            Err(AppError::PermissionDenied(format!(
+                "Path is outside the open workspace: {}",
+                requested
+            )))
+        }
+    }
+}
+
+/// Canonical path of a folder that may become a workspace root
+pub fn workspace_root(path: &str) -> AppResult<PathBuf> {
+    let root = fs::canonicalize(path)
+        .map_err(|e| AppError::NotFound(format!("Folder not found: {} ({})", path, e)))?;
+
+    if !root.is_dir() {
+        return Err(AppError::NotSupported(format!("Not a folder: {}", path)));
+    }
+    // Opening `/` or the home folder itself would make the guard pointless
+    if root.parent().is_none() || Some(root.as_path()) == home_dir().as_deref() {
+        return Err(AppError::PermissionDenied(format!(
+            "Refusing to open {} as a workspace root",
+            root.display()
+        )));
+    }
+    Ok(root)
+}
+
+/// Canonicalise a path that may not exist yet.
+///
+/// The deepest existing ancestor is canonicalised and the missing components
+/// are appended. `..` is only allowed in the part that exists, where the OS
+/// resolves it.
+fn canonicalize_lenient(path: &Path, follow_last: bool) -> AppResult<PathBuf> {
+    if !path.is_absolute() {
+        return Err(AppError::PermissionDenied(format!(
+            "Expected an absolute path: {}",
+            path.display()
+        )));
+    }
+
+    let mut existing = path;
+    let mut rest = Vec::new();
+    if !follow_last {
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            rest.push(name.to_os_string());
+            existing = parent;
+        }
+    }
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+
+    let mut resolved = fs::canonicalize(existing)?;
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+    Ok(resolved)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .map(PathBuf::from)
+        .and_then(|p| fs::canonicalize(p).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A workspace root with an `outside` folder next to it
+    fn setup(name: &str) -> (PathBuf, PathBuf, WorkspaceRoots) {
+        let base = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(base.join("root").join("src")).unwrap();
+        fs::create_dir_all(base.join("outside")).unwrap();
+        let base = fs::canonicalize(base).unwrap();
+        let roots = WorkspaceRoots::default();
+        roots.add(base.join("root"));
+        (base.clone(), base.join("root"), roots)
+    }
+
+    fn denied(result: AppResult<PathBuf>) -> bool {
+        matches!(result, Err(AppError::PermissionDenied(_)))
+    }
+
+    #[test]
+    fn resolves_paths_inside_the_roots() {
+        let (base, root, roots) = setup("sandbox-inside");
+        let path = |p: &Path| p.to_string_lossy().to_string();
+
+        assert_eq!(roots.resolve(&path(&root)).unwrap(), root);
+        assert_eq!(
+            roots.resolve(&path(&root.join("src/../a.txt"))).unwrap(),
+            root.join("a.txt")
+        );
+        // Files that do not exist yet, in folders that do not either
+        assert_eq!(
+            roots.resolve_entry(&path(&root.join("new/b.txt"))).unwrap(),
+            root.join("new/b.txt")
+        );
+
+        assert!(denied(roots.resolve(&path(&root.join("../outside/a.txt")))));
+        assert!(denied(
+            roots.resolve(&path(&root.join("src/../../outside")))
+        ));
+        assert!(denied(roots.resolve(&path(&base.join("outside")))));
+        assert!(denied(roots.resolve("src/a.txt")));
+        assert!(denied(roots.resolve_entry("../outside")));
+
+        roots.remove(&path(&root));
+        assert!(denied(roots.resolve(&path(&root.join("a.txt")))));
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_leave_the_roots() {
+        let (base, root, roots) = setup("sandbox-symlinks");
+        let path = |p: &Path| p.to_string_lossy().to_string();
+        std::os::unix::fs::symlink(base.join("outside"), root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("inner")).unwrap();
+
+        assert!(denied(roots.resolve(&path(&root.join("escape")))));
+        assert!(denied(roots.resolve(&path(&root.join("escape/a.txt")))));
+        assert!(denied(
+            roots.resolve_entry(&path(&root.join("escape/a.txt")))
+        ));
+        assert_eq!(
+            roots.resolve(&path(&root.join("inner/a.txt"))).unwrap(),
+            root.join("src/a.txt")
+        );
+
+        // The link itself can be renamed or deleted, its target is not touched
+        assert_eq!(
+            roots.resolve_entry(&path(&root.join("escape"))).unwrap(),
+            root.join("escape")
+        );
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn refuses_broad_workspace_roots() {
+        let (base, root, _) = setup("sandbox-workspace-root");
+        assert_eq!(workspace_root(&root.to_string_lossy()).unwrap(), root);
+        assert!(matches!(
+            workspace_root("/"),
+            Err(AppError::PermissionDenied(_))
+        ));
+        assert!(matches!(
+            workspace_root(&root.join("missing").to_string_lossy()),
+            Err(AppError::NotFound(_))
+        ));
+        fs::write(root.join("a.txt"), b"").unwrap();
+        assert!(matches!(
+            workspace_root(&root.join("a.txt").to_string_lossy()),
+            Err(AppError::NotSupported(_))
+        ));
+        let _ = fs::remove_dir_all(&base);
+    }
+}
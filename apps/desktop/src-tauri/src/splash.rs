@@ -0,0 +1,256 @@
+//! Launch screen resources for the mobile targets.
+//!
+//! Android 12 and later draw the splash screen themselves from the theme's
+//! `windowSplashScreen*` attributes, iOS shows the `LaunchScreen` storyboard.
+//! Both get the logo centred on a solid background color.
+
+use crate::errors::{AppError, AppResult};
+use crate::icons::{self, IconSource, OutputDirs};
+use image::Rgba;
+use rayon::prelude::*;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The splash icon is 288dp, and only a 192dp circle in its centre is shown.
+/// The logo gets the square inscribed in that circle so no corner is cut off.
+const ANDROID_SPLASH_DP: u32 = 288;
+const ANDROID_SPLASH_LOGO_DP: u32 = 136;
+
+/// Density buckets with their scale relative to mdpi, in quarters
+const ANDROID_DENSITY_QUARTERS: &[(&str, u32)] = &[
+    ("mdpi", 4),
+    ("hdpi", 6),
+    ("xhdpi", 8),
+    ("xxhdpi", 12),
+    ("xxxhdpi", 16),
+];
+
+/// Used when `values/themes.xml` has no style to extend (the `tauri android init` parent)
+const DEFAULT_THEME: (&str, &str) = ("Theme.App", "Theme.MaterialComponents.DayNight.NoActionBar");
+
+/// Size of the logo on the iOS launch screen in points
+const IOS_LOGO_POINTS: u32 = 200;
+
+/// Marks the Android XML files written here. A file at one of their paths
+/// without it was written by hand and is not replaced.
+const GENERATED: &str = "<!-- Generated splash screen resource, regenerating replaces it -->";
+
+const COLOR_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+{generated}
+<resources>
+    <color name="splash_background">{color}</color>
+</resources>
+"#;
+
+const SPLASH_ITEMS: &str = r#"        <item name="android:windowSplashScreenBackground">@color/splash_background</item>
+        <item name="android:windowSplashScreenAnimatedIcon">@drawable/splash_icon</item>
+"#;
+
+const LAUNCH_STORYBOARD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<document type="com.apple.InterfaceBuilder3.CocoaTouch.Storyboard.XIB" version="3.0" toolsVersion="21701" targetRuntime="iOS.CocoaTouch" propertyAccessControl="none" useAutolayout="YES" launchScreen="YES" useTraitCollections="YES" useSafeAreas="YES" colorMatched="YES" initialViewController="01J-lp-oVM">
+    <device id="retina6_12" orientation="portrait" appearance="light"/>
+    <dependencies>
+        <plugIn identifier="com.apple.InterfaceBuilder.IBCocoaTouchPlugin" version="21679"/>
+        <capability name="Named colors" minToolsVersion="9.0"/>
+        <capability name="Safe area layout guides" minToolsVersion="9.0"/>
+        <capability name="documents saved in the Xcode 8 format" minToolsVersion="8.0"/>
+    </dependencies>
+    <scenes>
+        <scene sceneID="EHf-IW-A2E">
+            <objects>
+                <viewController id="01J-lp-oVM" sceneMemberID="viewController">
+                    <view key="view" contentMode="scaleToFill" id="Ze5-6b-2t3">
+                        <rect key="frame" x="0.0" y="0.0" width="393" height="852"/>
+                        <autoresizingMask key="autoresizingMask" widthSizable="YES" heightSizable="YES"/>
+                        <subviews>
+                            <imageView clipsSubviews="YES" userInteractionEnabled="NO" contentMode="scaleAspectFit" image="LaunchScreen" translatesAutoresizingMaskIntoConstraints="NO" id="YRO-k0-Ey4">
+                                <rect key="frame" x="{x}" y="{y}" width="{size}" height="{size}"/>
+                                <constraints>
+                                    <constraint firstAttribute="width" constant="{size}" id="Wdt-7c-1Lq"/>
+                                    <constraint firstAttribute="height" constant="{size}" id="Hgt-2d-9Kp"/>
+                                </constraints>
+                            </imageView>
+                        </subviews>
+                        <viewLayoutGuide key="safeArea" id="Bcu-3y-fUS"/>
+                        <color key="backgroundColor" name="LaunchBackground"/>
+                        <constraints>
+                            <constraint firstItem="YRO-k0-Ey4" firstAttribute="centerX" secondItem="Ze5-6b-2t3" secondAttribute="centerX" id="CnX-4f-8Rt"/>
+                            <constraint firstItem="YRO-k0-Ey4" firstAttribute="centerY" secondItem="Ze5-6b-2t3" secondAttribute="centerY" id="CnY-5g-3Su"/>
+                        </constraints>
+                    </view>
+                </viewController>
+                <placeholder placeholderIdentifier="IBFirstResponder" id="iYj-Kq-Ea1" userLabel="First Responder" sceneMemberID="firstResponder"/>
+            </objects>
+            <point key="canvasLocation" x="53" y="375"/>
+        </scene>
+    </scenes>
+    <resources>
+        <image name="LaunchScreen" width="{size}" height="{size}"/>
+        <namedColor name="LaunchBackground">
+            <color red="{red}" green="{green}" blue="{blue}" alpha="1" colorSpace="custom" customColorSpace="sRGB"/>
+        </namedColor>
+    </resources>
+</document>
+"#;
+
+fn hex(color: Rgba<u8>) -> String {
+    format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2])
+}
+
+fn json_bytes(value: &serde_json::Value) -> AppResult<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// `values-v31/themes.xml`: the app theme from `values/themes.xml` with the
+/// splash screen attributes added, since a qualified style replaces the
+/// unqualified one as a whole
+fn android_theme(res: &Path) -> String {
+    let style =
+        Regex::new(r#"(?s)<style\s+name="([^"]+)"(?:\s+parent="([^"]*)")?\s*>(.*?)</style>"#)
+            .unwrap();
+    let splash_item =
+        Regex::new(r#"(?m)^\s*<item\s+name="android:windowSplashScreen\w*".*\n?"#).unwrap();
+
+    let themes = fs::read_to_string(res.join("values").join("themes.xml")).unwrap_or_default();
+    let (name, parent, body) = match style.captures(&themes) {
+        Some(caps) => (
+            caps[1].to_string(),
+            caps.get(2)
+                .map_or(String::new(), |p| p.as_str().to_string()),
+            splash_item.replace_all(&caps[3], "").trim_end().to_string(),
+        ),
+        None => (
+            DEFAULT_THEME.0.to_string(),
+            DEFAULT_THEME.1.to_string(),
+            String::new(),
+        ),
+    };
+
+    let parent = if parent.is_empty() {
+        String::new()
+    } else {
+        format!(r#" parent="{}""#, parent)
+    };
+    let body = if body.trim().is_empty() {
+        "\n".to_string()
+    } else {
+        format!("{}\n", body)
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n{}\n<resources>\n    <style name=\"{}\"{}>{}{}    </style>\n</resources>\n",
+        GENERATED, name, parent, body, SPLASH_ITEMS
+    )
+}
+
+/// Fail if `path` holds a file that was not generated here
+fn ensure_generated(path: &Path) -> AppResult<()> {
+    match fs::read_to_string(path) {
+        Ok(content) if !content.contains(GENERATED) => Err(AppError::AlreadyExists(format!(
+            "{} was not generated here, add the splash screen attributes to it by hand or move it away",
+            path.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Write the Android 12 splash screen and the iOS launch screen for `logo`
+/// on `background` into the `gen/` output folders, returning the files
+/// written. Nothing is written when `values/splash.xml` or
+/// `values-v31/themes.xml` exists without being generated here.
+pub fn generate(
+    logo: &IconSource,
+    background: Rgba<u8>,
+    dirs: &OutputDirs,
+) -> AppResult<Vec<PathBuf>> {
+    let background = Rgba([background[0], background[1], background[2], 255]);
+    let mut renders: Vec<(PathBuf, u32, u32)> = Vec::new();
+    let mut files: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+
+    let res = &dirs.android_res;
+    for &(density, quarters) in ANDROID_DENSITY_QUARTERS {
+        renders.push((
+            res.join(format!("drawable-{}", density))
+                .join("splash_icon.png"),
+            ANDROID_SPLASH_DP * quarters / 4,
+            ANDROID_SPLASH_LOGO_DP * quarters / 4,
+        ));
+    }
+    let colors = res.join("values").join("splash.xml");
+    let theme = res.join("values-v31").join("themes.xml");
+    ensure_generated(&colors)?;
+    ensure_generated(&theme)?;
+    let color_xml = COLOR_XML
+        .replace("{generated}", GENERATED)
+        .replace("{color}", &hex(background));
+    files.push((colors, color_xml.into_bytes()));
+    files.push((theme, android_theme(res).into_bytes()));
+
+    let apple = &dirs.apple;
+    let catalog = apple.join("Assets.xcassets");
+    let image_set = catalog.join("LaunchScreen.imageset");
+    let mut images = Vec::new();
+    for scale in 1..=3 {
+        let name = format!("LaunchScreen@{}x.png", scale);
+        let size = IOS_LOGO_POINTS * scale;
+        renders.push((image_set.join(&name), size, size));
+        images.push(serde_json::json!({
+            "filename": name,
+            "idiom": "universal",
+            "scale": format!("{}x", scale),
+        }));
+    }
+    let info = serde_json::json!({ "author": "xcode", "version": 1 });
+    files.push((
+        image_set.join("Contents.json"),
+        json_bytes(&serde_json::json!({ "images": images, "info": info }))?,
+    ));
+
+    let component = |c: u8| format!("{:.3}", c as f32 / 255.0);
+    files.push((
+        catalog
+            .join("LaunchBackground.colorset")
+            .join("Contents.json"),
+        json_bytes(&serde_json::json!({
+            "colors": [{
+                "color": {
+                    "color-space": "srgb",
+                    "components": {
+                        "alpha": "1.000",
+                        "red": component(background[0]),
+                        "green": component(background[1]),
+                        "blue": component(background[2]),
+                    },
+                },
+                "idiom": "universal",
+            }],
+            "info": info,
+        }))?,
+    ));
+
+    let size = IOS_LOGO_POINTS as f32;
+    let storyboard = LAUNCH_STORYBOARD
+        .replace("{x}", &format!("{}", (393.0 - size) / 2.0))
+        .replace("{y}", &format!("{}", (852.0 - size) / 2.0))
+        .replace("{size}", &IOS_LOGO_POINTS.to_string())
+        .replace("{red}", &component(background[0]))
+        .replace("{green}", &component(background[1]))
+        .replace("{blue}", &component(background[2]));
+    files.push((
+        apple.join("LaunchScreen.storyboard"),
+        storyboard.into_bytes(),
+    ));
+
+    let rendered: Vec<(PathBuf, Vec<u8>)> = renders
+        .into_par_iter()
+        .map(|(path, size, content)| {
+            Ok((path, icons::encode_png(&logo.render_padded(size, content))?))
+        })
+        .collect::<AppResult<_>>()?;
+    files.extend(rendered);
+
+    for (path, bytes) in &files {
+        dirs.write(path, bytes)?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
@@ -0,0 +1,431 @@
+use crate::commands::search::walk_workspace;
+use crate::file_io;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tree_sitter::{Language, Node, Parser};
+
+/// Files larger than this are most likely generated and are not indexed
+const MAX_SOURCE_SIZE: u64 = 1024 * 1024;
+
+/// Score added when the symbol name equals the query, ignoring case
+const EXACT_NAME_BONUS: i64 = 100;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: String, // function, method, command, struct, enum, trait, class, interface, type, ...
+    pub container: Option<String>, // enclosing impl, trait, mod or class
+    pub file: String,
+    pub line: usize,   // 1-based position of the name
+    pub column: usize, // UTF-16 column of the name
+    pub end_line: usize,
+    pub exported: bool, // `pub` in Rust, `export` in TypeScript
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SourceKind {
+    Rust,
+    TypeScript,
+    Tsx,
+    Svelte,
+}
+
+fn source_kind(path: &Path) -> Option<SourceKind> {
+    match path.extension()?.to_str()? {
+        "rs" => Some(SourceKind::Rust),
+        "ts" | "mts" | "cts" => Some(SourceKind::TypeScript),
+        "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(SourceKind::Tsx),
+        "svelte" => Some(SourceKind::Svelte),
+        _ => None,
+    }
+}
+
+/// Whether symbols can be extracted from this file
+pub fn is_supported(path: &Path) -> bool {
+    source_kind(path).is_some()
+}
+
+/// Walks a syntax tree and collects the symbols of one file
+struct Extractor<'a> {
+    source: &'a str,
+    file: &'a str,
+    line_offset: usize,  // for Svelte script blocks, the line the block starts on
+    first_column: usize, // and the column of its first line
+    symbols: Vec<Symbol>,
+}
+
+impl<'a> Extractor<'a> {
+    fn text(&self, node: Node) -> &'a str {
+        &self.source[node.byte_range()]
+    }
+
+    fn push(
+        &mut self,
+        item: Node,
+        name: Node,
+        kind: &str,
+        container: &Option<String>,
+        exported: bool,
+    ) {
+        let row = name.start_position().row;
+        let line_start = name.start_byte() - name.start_position().column;
+        let mut column = self.source[line_start..name.start_byte()]
+            .encode_utf16()
+            .count();
+        if row == 0 {
+            column += self.first_column;
+        }
+
+        self.symbols.push(Symbol {
+            name: self.text(name).to_string(),
+            kind: kind.to_string(),
+            container: container.clone(),
+            file: self.file.to_string(),
+            line: self.line_offset + row + 1,
+            column,
+            end_line: self.line_offset + item.end_position().row + 1,
+            exported,
+        });
+    }
+
+    fn rust(&mut self, node: Node, container: &Option<String>) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            let exported = child
+                .named_child(0)
+                .is_some_and(|first| first.kind() == "visibility_modifier");
+            let name = child.child_by_field_name("name");
+
+            match (child.kind(), name) {
+                ("function_item" | "function_signature_item", Some(name)) => {
+                    let kind = if is_tauri_command(child, self.source) {
+                        "command"
+                    } else if container.is_some() {
+                        "method"
+                    } else {
+                        "function"
+                    };
+                    self.push(child, name, kind, container, exported);
+                }
+                ("struct_item", Some(name)) => {
+                    self.push(child, name, "struct", container, exported)
+                }
+                ("enum_item", Some(name)) => self.push(child, name, "enum", container, exported),
+                ("union_item", Some(name)) => self.push(child, name, "union", container, exported),
+                ("type_item", Some(name)) => self.push(child, name, "type", container, exported),
+                ("const_item" | "static_item", Some(name)) => {
+                    self.push(child, name, "constant", container, exported)
+                }
+                ("macro_definition", Some(name)) => {
+                    self.push(child, name, "macro", container, exported)
+                }
+                ("trait_item", Some(name)) => {
+                    self.push(child, name, "trait", container, exported);
+                    if let Some(body) = child.child_by_field_name("body") {
+                        self.rust(body, &Some(self.text(name).to_string()));
+                    }
+                }
+                ("mod_item", Some(name)) => {
+                    self.push(child, name, "module", container, exported);
+                    if let Some(body) = child.child_by_field_name("body") {
+                        self.rust(body, &Some(self.text(name).to_string()));
+                    }
+                }
+                ("impl_item", _) => {
+                    if let (Some(ty), Some(body)) = (
+                        child.child_by_field_name("type"),
+                        child.child_by_field_name("body"),
+                    ) {
+                        self.rust(body, &Some(self.text(ty).to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn typescript(&mut self, node: Node, container: &Option<String>, exported: bool) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            let name = child.child_by_field_name("name");
+
+            match (child.kind(), name) {
+                ("export_statement", _) => self.typescript(child, container, true),
+                (
+                    "function_declaration"
+                    | "generator_function_declaration"
+                    | "function_signature",
+                    Some(name),
+                ) => self.push(child, name, "function", container, exported),
+                ("class_declaration" | "abstract_class_declaration", Some(name)) => {
+                    self.push(child, name, "class", container, exported);
+                    if let Some(body) = child.child_by_field_name("body") {
+                        self.typescript(body, &Some(self.text(name).to_string()), false);
+                    }
+                }
+                ("method_definition" | "abstract_method_signature", Some(name)) => {
+                    self.push(child, name, "method", container, exported)
+                }
+                ("interface_declaration", Some(name)) => {
+                    self.push(child, name, "interface", container, exported)
+                }
+                ("type_alias_declaration", Some(name)) => {
+                    self.push(child, name, "type", container, exported)
+                }
+                ("enum_declaration", Some(name)) => {
+                    self.push(child, name, "enum", container, exported)
+                }
+                ("lexical_declaration" | "variable_declaration", _) if container.is_none() => {
+                    let mut declarators = child.walk();
+                    for declarator in child.named_children(&mut declarators) {
+                        let Some(name) = declarator.child_by_field_name("name") else {
+                            continue;
+                        };
+                        if name.kind() != "identifier" {
+                            continue; // destructuring
+                        }
+                        let is_function =
+                            declarator.child_by_field_name("value").is_some_and(|v| {
+                                matches!(
+                                    v.kind(),
+                                    "arrow_function" | "function_expression" | "function"
+                                )
+                            });
+                        let kind = if is_function { "function" } else { "variable" };
+                        self.push(declarator, name, kind, container, exported);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether a Rust function is preceded by `#[tauri::command]`
+fn is_tauri_command(function: Node, source: &str) -> bool {
+    let mut sibling = function.prev_named_sibling();
+    while let Some(node) = sibling {
+        match node.kind() {
+            "attribute_item" => {
+                let text = &source[node.byte_range()];
+                if text.contains("tauri::command") || text.replace(' ', "") == "#[command]" {
+                    return true;
+                }
+            }
+            "line_comment" | "block_comment" => {}
+            _ => break,
+        }
+        sibling = node.prev_named_sibling();
+    }
+    false
+}
+
+fn language(kind: SourceKind) -> Language {
+    match kind {
+        SourceKind::Rust => tree_sitter_rust::LANGUAGE.into(),
+        SourceKind::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+        SourceKind::TypeScript | SourceKind::Svelte => {
+            tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()
+        }
+    }
+}
+
+/// `<script>` blocks of a Svelte component: byte offset of the content and the content
+fn svelte_scripts(source: &str) -> Vec<(usize, &str)> {
+    static SCRIPT: OnceLock<Regex> = OnceLock::new();
+    let script =
+        SCRIPT.get_or_init(|| Regex::new(r"(?s)<script(?:\s[^>]*)?>(.*?)</script>").unwrap());
+    script
+        .captures_iter(source)
+        .filter_map(|caps| caps.get(1))
+        .map(|m| (m.start(), m.as_str()))
+        .collect()
+}
+
+/// Extract the symbols of a source file in document order
+pub fn extract(parser: &mut Parser, path: &Path, source: &str) -> Vec<Symbol> {
+    let Some(kind) = source_kind(path) else {
+        return Vec::new();
+    };
+    if parser.set_language(&language(kind)).is_err() {
+        return Vec::new();
+    }
+
+    let file = path.to_string_lossy();
+    let blocks = if kind == SourceKind::Svelte {
+        svelte_scripts(source)
+    } else {
+        vec![(0, source)]
+    };
+
+    let mut symbols = Vec::new();
+    for (offset, block) in blocks {
+        let Some(tree) = parser.parse(block, None) else {
+            continue;
+        };
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let mut extractor = Extractor {
+            source: block,
+            file: &file,
+            line_offset: before.matches('\n').count(),
+            first_column: before[line_start..].encode_utf16().count(),
+            symbols: Vec::new(),
+        };
+        match kind {
+            SourceKind::Rust => extractor.rust(tree.root_node(), &None),
+            _ => extractor.typescript(tree.root_node(), &None, false),
+        }
+        symbols.extend(extractor.symbols);
+    }
+    symbols
+}
+
+/// Read and parse a file, `None` if it is too large, binary or not UTF-8
+pub fn file_symbols(parser: &mut Parser, path: &Path) -> Option<Vec<Symbol>> {
+    if fs::metadata(path).ok()?.len() > MAX_SOURCE_SIZE {
+        return None;
+    }
+    let bytes = fs::read(path).ok()?;
+    if file_io::is_binary(&bytes) {
+        return None;
+    }
+    let source = String::from_utf8(bytes).ok()?;
+    Some(extract(parser, path, &source))
+}
+
+struct CachedSymbols {
+    mtime: u64,
+    size: u64,
+    symbols: Vec<Symbol>,
+}
+
+/// Bring the cached symbols of a workspace up to date with the files on disk.
+///
+/// Files are re-parsed only when their size or mtime changed, in parallel.
+fn refresh(cache: &mut HashMap<PathBuf, CachedSymbols>, roots: &[PathBuf], skip: &[String]) {
+    let sources: Vec<(PathBuf, u64, u64)> = roots
+        .iter()
+        .flat_map(|root| walk_workspace(root, skip, false))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()) && is_supported(e.path()))
+        .filter_map(|e| {
+            let metadata = e.metadata().ok()?;
+            (metadata.len() <= MAX_SOURCE_SIZE).then(|| {
+                (
+                    e.into_path(),
+                    file_io::modified_millis(&metadata),
+                    metadata.len(),
+                )
+            })
+        })
+        .collect();
+
+    let present: HashSet<&PathBuf> = sources.iter().map(|(p, _, _)| p).collect();
+    cache.retain(|path, _| present.contains(path));
+
+    let stale: Vec<_> = sources
+        .into_iter()
+        .filter(|(path, mtime, size)| {
+            cache
+                .get(path)
+                .map_or(true, |c| c.mtime != *mtime || c.size != *size)
+        })
+        .collect();
+    let parsed: Vec<_> = stale
+        .into_par_iter()
+        .map_init(Parser::new, |parser, (path, mtime, size)| {
+            let symbols = file_symbols(parser, &path).unwrap_or_default();
+            (
+                path,
+                CachedSymbols {
+                    mtime,
+                    size,
+                    symbols,
+                },
+            )
+        })
+        .collect();
+    cache.extend(parsed);
+}
+
+/// Symbols of every supported source file, cached per workspace
+#[derive(Default)]
+pub struct SymbolIndex {
+    workspaces: Mutex<HashMap<String, HashMap<PathBuf, CachedSymbols>>>,
+}
+
+impl SymbolIndex {
+    /// Fuzzy-match `query` against symbol names in a workspace
+    pub fn search(
+        &self,
+        workspace_id: &str,
+        roots: &[PathBuf],
+        skip: &[String],
+        query: &str,
+        limit: usize,
+    ) -> Vec<Symbol> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let cache = workspaces.entry(workspace_id.to_string()).or_default();
+        refresh(cache, roots, skip);
+
+        let matcher = SkimMatcherV2::default();
+        let mut scored: Vec<(i64, &Symbol)> = cache
+            .values()
+            .flat_map(|c| &c.symbols)
+            .filter_map(|symbol| {
+                let score = matcher.fuzzy_match(&symbol.name, query)?;
+                let exact = symbol.name.eq_ignore_ascii_case(query);
+                Some((score + if exact { EXACT_NAME_BONUS } else { 0 }, symbol))
+            })
+            .collect();
+
+        scored.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.1.name.len().cmp(&b.1.name.len()))
+                .then_with(|| a.1.file.cmp(&b.1.file))
+        });
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, symbol)| symbol.clone())
+            .collect()
+    }
+
+    /// Every symbol of a workspace for which `keep` returns true
+    pub fn filter<F: Fn(&Symbol) -> bool>(
+        &self,
+        workspace_id: &str,
+        roots: &[PathBuf],
+        skip: &[String],
+        keep: F,
+    ) -> Vec<Symbol> {
+        let mut workspaces = self.workspaces.lock().unwrap();
+        let cache = workspaces.entry(workspace_id.to_string()).or_default();
+        refresh(cache, roots, skip);
+
+        cache
+            .values()
+            .flat_map(|c| &c.symbols)
+            .filter(|symbol| keep(symbol))
+            .cloned()
+            .collect()
+    }
+
+    /// Drop the cached symbols of a workspace
+    pub fn forget(&self, workspace_id: &str) {
+        self.workspaces.lock().unwrap().remove(workspace_id);
+    }
+}
@@ -0,0 +1,427 @@
+//! Downscaled previews and metadata of images, cached on disk.
+//!
+//! Thumbnails live in `<app_cache_dir>/thumbnails`, named after a hash of
+//! the source path, its modification time and the thumbnail size, so an
+//! edited file simply gets a new entry. The metadata is cached next to the
+//! PNG so a cache hit does not touch the source at all.
+//!
+//! Entries unused for `MAX_AGE` are dropped when the cache is first used in
+//! a session, then the least recently used ones until it fits `MAX_BYTES`.
+
+use crate::errors::{AppError, AppResult};
+use crate::file_io;
+use crate::icons::{self, IconSource};
+use image::{AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager};
+
+/// Largest thumbnail edge that can be requested
+const MAX_SIZE: u32 = 512;
+
+/// Entries not used for this long are pruned
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Size the cache is pruned down to
+const MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Length of the key that every file of an entry starts with
+const KEY_LEN: usize = 32;
+
+/// Thumbnail cache folder, created and added to the asset protocol scope on first use
+#[derive(Default)]
+pub struct ThumbnailCache {
+    dir: OnceLock<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ImageMetadata {
+    format: String,
+    width: u32,
+    height: u32,
+    color_type: Option<String>, // as decoded, e.g. Rgba8 or L16; none for SVG
+    bit_depth: Option<u8>,      // bits per channel
+    frames: u32,                // more than 1 for animated GIF, PNG and WebP
+    orientation: u8,            // EXIF orientation, 1 (upright) when there is none
+}
+
+/// One of the sizes stored in an ICO or ICNS file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EmbeddedImage {
+    width: u32,
+    height: u32,
+    bit_depth: Option<u8>, // bits per pixel, as stored
+    path: String,          // cached PNG of this size
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Thumbnail {
+    path: String, // cached PNG, at most `size` pixels on the longer edge
+    metadata: ImageMetadata,
+    embedded: Vec<EmbeddedImage>, // every size of an ICO or ICNS, largest first
+}
+
+impl ThumbnailCache {
+    fn dir(&self, app: &AppHandle) -> AppResult<&Path> {
+        if let Some(dir) = self.dir.get() {
+            return Ok(dir);
+        }
+        let dir = app
+            .path()
+            .app_cache_dir()
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .join("thumbnails");
+        fs::create_dir_all(&dir)?;
+        prune(&dir, MAX_AGE, MAX_BYTES);
+        app.asset_protocol_scope()
+            .allow_directory(&dir, false)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        Ok(self.dir.get_or_init(|| dir))
+    }
+
+    /// Thumbnail and metadata of the image at `path`, from the cache when
+    /// the file has not changed since
+    pub fn get(&self, app: &AppHandle, path: &Path, size: u32) -> AppResult<Thumbnail> {
+        let size = size.clamp(16, MAX_SIZE);
+        let metadata = fs::metadata(path)?;
+        let key = file_io::content_hash(
+            format!(
+                "{}\n{}\n{}\n{}",
+                path.display(),
+                file_io::modified_millis(&metadata),
+                metadata.len(),
+                size
+            )
+            .as_bytes(),
+        );
+        let dir = self.dir(app)?;
+        let key = &key[..KEY_LEN];
+        let entry = dir.join(format!("{}.json", key));
+
+        if let Some(cached) = fs::read(&entry)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Thumbnail>(&json).ok())
+            .filter(|t| Path::new(&t.path).exists())
+        {
+            // The metadata file's modification time is when the entry was last used
+            if let Ok(file) = File::options().append(true).open(&entry) {
+                let _ = file.set_modified(SystemTime::now());
+            }
+            return Ok(cached);
+        }
+
+        let thumbnail = render(path, size, &dir.join(key))?;
+        let json = serde_json::to_vec(&thumbnail).map_err(|e| AppError::Internal(e.to_string()))?;
+        file_io::atomic_write(&entry, &json)?;
+        Ok(thumbnail)
+    }
+}
+
+/// Delete entries last used more than `max_age` ago, then the least
+/// recently used ones until the cache holds at most `max_bytes`.
+///
+/// All files of an entry (metadata, thumbnail and embedded sizes) go together.
+/// Failures are ignored, the cache only has to shrink eventually.
+fn prune(dir: &Path, max_age: Duration, max_bytes: u64) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    // key -> (files, last used, bytes)
+    let mut entries: std::collections::HashMap<String, (Vec<PathBuf>, SystemTime, u64)> =
+        std::collections::HashMap::new();
+    for file in read_dir.flatten() {
+        let name = file.file_name().to_string_lossy().to_string();
+        let Some(key) = name.get(..KEY_LEN) else {
+            continue;
+        };
+        let Ok(metadata) = file.metadata() else {
+            continue;
+        };
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let entry = entries
+            .entry(key.to_string())
+            .or_insert_with(|| (Vec::new(), SystemTime::UNIX_EPOCH, 0));
+        entry.0.push(file.path());
+        entry.1 = entry.1.max(modified);
+        entry.2 += metadata.len();
+    }
+
+    let mut entries: Vec<_> = entries.into_values().collect();
+    entries.sort_by_key(|(_, used, _)| std::cmp::Reverse(*used));
+    let now = SystemTime::now();
+    let mut kept = 0;
+    for (files, used, bytes) in entries {
+        kept += bytes;
+        if kept <= max_bytes && now.duration_since(used).unwrap_or_default() <= max_age {
+            continue;
+        }
+        for file in files {
+            let _ = fs::remove_file(file);
+        }
+    }
+}
+
+fn decode_error(path: &Path, e: impl std::fmt::Display) -> AppError {
+    AppError::NotSupported(format!("Cannot decode {}: {}", path.display(), e))
+}
+
+fn save(image: &RgbaImage, path: PathBuf) -> AppResult<String> {
+    icons::write(&path, &icons::encode_png(image)?)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Fit `image` into a `size` square, never scaling up
+fn shrink(image: &DynamicImage, size: u32) -> RgbaImage {
+    if image.width() <= size && image.height() <= size {
+        image.to_rgba8()
+    } else {
+        image.thumbnail(size, size).to_rgba8()
+    }
+}
+
+/// Frames of an animation, 1 for still images and formats without animation
+fn frame_count(path: &Path, format: ImageFormat) -> AppResult<u32> {
+    let reader = || -> AppResult<BufReader<File>> { Ok(BufReader::new(File::open(path)?)) };
+    let frames = match format {
+        ImageFormat::Gif => image::codecs::gif::GifDecoder::new(reader()?)
+            .map_err(|e| decode_error(path, e))?
+            .into_frames()
+            .count(),
+        ImageFormat::Png => {
+            let decoder = image::codecs::png::PngDecoder::new(reader()?)
+                .map_err(|e| decode_error(path, e))?;
+            if decoder.is_apng().map_err(|e| decode_error(path, e))? {
+                decoder
+                    .apng()
+                    .map_err(|e| decode_error(path, e))?
+                    .into_frames()
+                    .count()
+            } else {
+                1
+            }
+        }
+        ImageFormat::WebP => {
+            let decoder = image::codecs::webp::WebPDecoder::new(reader()?)
+                .map_err(|e| decode_error(path, e))?;
+            if decoder.has_animation() {
+                decoder.into_frames().count()
+            } else {
+                1
+            }
+        }
+        _ => 1,
+    };
+    Ok(frames as u32)
+}
+
+fn render(path: &Path, size: u32, stem: &Path) -> AppResult<Thumbnail> {
+    let thumbnail_path = stem.with_extension("png");
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "svg" | "svgz" => return render_svg(path, size, thumbnail_path),
+        "icns" => return render_icns(path, size, stem),
+        _ => {}
+    }
+
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = reader.format().ok_or_else(|| {
+        AppError::NotSupported(format!("Unknown image format: {}", path.display()))
+    })?;
+    if format == ImageFormat::Ico {
+        return render_ico(path, size, stem);
+    }
+
+    let mut decoder = reader.into_decoder().map_err(|e| decode_error(path, e))?;
+    let (width, height) = decoder.dimensions();
+    let color = decoder.original_color_type();
+    let orientation = decoder.orientation().map_err(|e| decode_error(path, e))?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| decode_error(path, e))?;
+    image.apply_orientation(orientation);
+
+    Ok(Thumbnail {
+        path: save(&shrink(&image, size), thumbnail_path)?,
+        metadata: ImageMetadata {
+            format: format.extensions_str().first().unwrap_or(&"").to_string(),
+            width,
+            height,
+            color_type: Some(format!("{:?}", color)),
+            bit_depth: Some((color.bits_per_pixel() / color.channel_count().max(1) as u16) as u8),
+            // The first frame is decoded already, an animation that cannot be counted shows just that
+            frames: frame_count(path, format).unwrap_or_else(|e| {
+                log::warn!("Cannot count the frames of {}: {}", path.display(), e);
+                1
+            }),
+            orientation: orientation.to_exif(),
+        },
+        embedded: Vec::new(),
+    })
+}
+
+fn render_svg(path: &Path, size: u32, thumbnail_path: PathBuf) -> AppResult<Thumbnail> {
+    let source = IconSource::open(path)?;
+    let (width, height) = source.dimensions();
+    // Rasterized at the thumbnail size, then cut to the aspect ratio
+    let scale = size as f32 / width.max(height);
+    let (w, h) = (
+        ((width * scale).round() as u32).clamp(1, size),
+        ((height * scale).round() as u32).clamp(1, size),
+    );
+    let cropped =
+        image::imageops::crop_imm(&source.render(size), (size - w) / 2, (size - h) / 2, w, h)
+            .to_image();
+
+    Ok(Thumbnail {
+        path: save(&cropped, thumbnail_path)?,
+        metadata: ImageMetadata {
+            format: "svg".to_string(),
+            width: width.round() as u32,
+            height: height.round() as u32,
+            color_type: None,
+            bit_depth: None,
+            frames: 1,
+            orientation: 1,
+        },
+        embedded: Vec::new(),
+    })
+}
+
+/// Thumbnail and metadata from the largest of the embedded sizes
+fn from_embedded(
+    format: &str,
+    mut images: Vec<(RgbaImage, Option<u8>)>,
+    size: u32,
+    stem: &Path,
+) -> AppResult<Thumbnail> {
+    images.sort_by_key(|(image, _)| std::cmp::Reverse(image.width() * image.height()));
+    let Some((largest, _)) = images.first() else {
+        return Err(AppError::NotSupported(format!(
+            "No images in {}",
+            stem.display()
+        )));
+    };
+    let (width, height) = largest.dimensions();
+    let path = save(
+        &shrink(&DynamicImage::ImageRgba8(largest.clone()), size),
+        stem.with_extension("png"),
+    )?;
+
+    let mut embedded = Vec::new();
+    for (i, (image, bit_depth)) in images.iter().enumerate() {
+        // ICNS can hold the same pixel size twice, as 2x and as 1x of the next size
+        let name = format!(
+            "{}-{}.png",
+            stem.file_name().unwrap_or_default().to_string_lossy(),
+            i
+        );
+        embedded.push(EmbeddedImage {
+            width: image.width(),
+            height: image.height(),
+            bit_depth: *bit_depth,
+            path: save(image, stem.with_file_name(name))?,
+        });
+    }
+
+    Ok(Thumbnail {
+        path,
+        metadata: ImageMetadata {
+            format: format.to_string(),
+            width,
+            height,
+            color_type: Some("Rgba8".to_string()),
+            bit_depth: Some(8),
+            frames: 1,
+            orientation: 1,
+        },
+        embedded,
+    })
+}
+
+fn render_ico(path: &Path, size: u32, stem: &Path) -> AppResult<Thumbnail> {
+    let dir =
+        ico::IconDir::read(BufReader::new(File::open(path)?)).map_err(|e| decode_error(path, e))?;
+    let images = dir
+        .entries()
+        .iter()
+        .filter_map(|entry| {
+            let image = entry.decode().ok()?;
+            let rgba =
+                RgbaImage::from_raw(image.width(), image.height(), image.rgba_data().to_vec())?;
+            Some((rgba, Some(entry.bits_per_pixel() as u8)))
+        })
+        .collect();
+    from_embedded("ico", images, size, stem)
+}
+
+fn render_icns(path: &Path, size: u32, stem: &Path) -> AppResult<Thumbnail> {
+    let family = icns::IconFamily::read(BufReader::new(File::open(path)?))
+        .map_err(|e| decode_error(path, e))?;
+    let images = family
+        .available_icons()
+        .into_iter()
+        .filter_map(|icon_type| {
+            let image = family.get_icon_with_type(icon_type).ok()?;
+            let image = image.convert_to(icns::PixelFormat::RGBA);
+            let rgba =
+                RgbaImage::from_raw(image.width(), image.height(), image.into_data().into_vec())?;
+            let bits = match icon_type.encoding() {
+                icns::Encoding::RLE24 => 24,
+                _ => 32,
+            };
+            Some((rgba, Some(bits)))
+        })
+        .collect();
+    from_embedded("icns", images, size, stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &Path, bytes: usize, age: Duration) {
+        fs::write(path, vec![0; bytes]).unwrap();
+        let file = File::options().append(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn prunes_stale_then_least_recently_used_entries() {
+        let dir = std::env::temp_dir().join(format!("thumbnails-prune-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key = |c: char| c.to_string().repeat(KEY_LEN);
+        let hour = Duration::from_secs(60 * 60);
+
+        // Newest, its embedded size was written earlier than the metadata
+        touch(&dir.join(format!("{}.json", key('a'))), 10, Duration::ZERO);
+        touch(&dir.join(format!("{}-0.png", key('a'))), 40, 3 * hour);
+        touch(&dir.join(format!("{}.png", key('b'))), 50, hour);
+        touch(&dir.join(format!("{}.png", key('c'))), 50, 2 * hour);
+        touch(&dir.join(format!("{}.png", key('d'))), 1, 1000 * hour);
+        touch(&dir.join("other"), 1000, Duration::ZERO);
+
+        prune(&dir, 100 * hour, 120);
+        let mut left: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|f| f.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![
+                format!("{}-0.png", key('a')),
+                format!("{}.json", key('a')),
+                format!("{}.png", key('b')),
+                "other".to_string(),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,635 @@
+use crate::commands::search::{walk_workspace, SearchOptions};
+use crate::errors::{AppError, AppResult};
+use crate::file_io;
+use crate::models::Workspace;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use regex_syntax::hir::{Hir, HirKind};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tauri::{AppHandle, Emitter, Manager};
+
+const MAGIC: &[u8; 4] = b"FTI1";
+
+/// Larger files are not indexed and are always searched
+const MAX_INDEXED_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Files whose trigrams are computed in parallel before merging them into the index
+const INDEX_CHUNK_SIZE: usize = 256;
+
+/// Shortest literal that narrows a search
+const TRIGRAM_LEN: usize = 3;
+
+struct IndexedFile {
+    path: PathBuf,
+    mtime: u64,
+    size: u64,
+    indexed: bool, // false for files too large to index, which always have to be searched
+}
+
+/// Which files contain which byte trigrams, ASCII case-folded.
+///
+/// Binary and non-UTF-8 files are indexed without trigrams since search
+/// never matches them anyway.
+#[derive(Default)]
+pub struct TrigramIndex {
+    files: Vec<IndexedFile>,
+    postings: HashMap<u32, Vec<u32>>, // trigram -> sorted file ids
+}
+
+fn trigram(window: &[u8]) -> u32 {
+    let fold = |b: u8| b.to_ascii_lowercase() as u32;
+    (fold(window[0]) << 16) | (fold(window[1]) << 8) | fold(window[2])
+}
+
+fn trigrams(bytes: &[u8]) -> Vec<u32> {
+    let mut set: Vec<u32> = bytes.windows(TRIGRAM_LEN).map(trigram).collect();
+    set.sort_unstable();
+    set.dedup();
+    set
+}
+
+/// Trigrams of a text file, empty for files search would skip
+fn file_trigrams(path: &Path) -> Vec<u32> {
+    match fs::read(path) {
+        Ok(bytes) if !file_io::is_binary(&bytes) && std::str::from_utf8(&bytes).is_ok() => {
+            trigrams(&bytes)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Literal runs every match of `hir` has to contain
+fn required_literals(hir: &Hir) -> Vec<Vec<u8>> {
+    match hir.kind() {
+        HirKind::Literal(literal) => vec![literal.0.to_vec()],
+        HirKind::Capture(capture) => required_literals(&capture.sub),
+        HirKind::Repetition(repetition) if repetition.min > 0 => required_literals(&repetition.sub),
+        HirKind::Concat(parts) => {
+            let mut runs = Vec::new();
+            let mut current = Vec::new();
+            for part in parts {
+                if let HirKind::Literal(literal) = part.kind() {
+                    current.extend_from_slice(&literal.0);
+                } else {
+                    runs.push(std::mem::take(&mut current));
+                    runs.extend(required_literals(part));
+                }
+            }
+            runs.push(current);
+            runs
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Trigrams a file must contain to match the query, empty when the query cannot be narrowed.
+///
+/// Only ASCII is case-folded in the index, so case-insensitive searches
+/// ignore trigrams with other characters.
+pub fn required_trigrams(query: &str, options: &SearchOptions) -> Vec<u32> {
+    let runs = if options.regex {
+        match regex_syntax::parse(query) {
+            Ok(hir) => required_literals(&hir),
+            Err(_) => Vec::new(),
+        }
+    } else {
+        vec![query.as_bytes().to_vec()]
+    };
+
+    let mut required: Vec<u32> = runs
+        .iter()
+        .flat_map(|run| run.windows(TRIGRAM_LEN))
+        .filter(|window| options.case_sensitive || window.is_ascii())
+        .map(trigram)
+        .collect();
+    required.sort_unstable();
+    required.dedup();
+    required
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte < 0x80 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+impl TrigramIndex {
+    /// Load a saved index, `None` if missing or unreadable
+    fn load(path: &Path) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        if !bytes.starts_with(MAGIC) {
+            return None;
+        }
+        let pos = &mut MAGIC.len();
+
+        let mut index = TrigramIndex::default();
+        let file_count = read_varint(&bytes, pos)?;
+        for _ in 0..file_count {
+            let len = read_varint(&bytes, pos)? as usize;
+            let mtime = read_varint(&bytes, pos)?;
+            let size = read_varint(&bytes, pos)?;
+            let indexed = read_varint(&bytes, pos)? == 1;
+            let name = bytes.get(*pos..*pos + len)?;
+            *pos += len;
+            index.files.push(IndexedFile {
+                path: PathBuf::from(String::from_utf8(name.to_vec()).ok()?),
+                mtime,
+                size,
+                indexed,
+            });
+        }
+
+        let posting_count = read_varint(&bytes, pos)?;
+        for _ in 0..posting_count {
+            let trigram = read_varint(&bytes, pos)? as u32;
+            let count = read_varint(&bytes, pos)?;
+            let mut ids = Vec::with_capacity(count.min(index.files.len() as u64) as usize);
+            let mut id = 0;
+            for _ in 0..count {
+                id += read_varint(&bytes, pos)? as u32;
+                if id as usize >= index.files.len() {
+                    return None;
+                }
+                ids.push(id);
+            }
+            index.postings.insert(trigram, ids);
+        }
+        Some(index)
+    }
+
+    /// Save as varints with delta-encoded posting lists
+    fn save(&self, path: &Path) -> AppResult<()> {
+        let mut out = MAGIC.to_vec();
+        write_varint(&mut out, self.files.len() as u64);
+        for file in &self.files {
+            let name = file.path.to_string_lossy();
+            write_varint(&mut out, name.len() as u64);
+            write_varint(&mut out, file.mtime);
+            write_varint(&mut out, file.size);
+            write_varint(&mut out, file.indexed as u64);
+            out.extend_from_slice(name.as_bytes());
+        }
+
+        write_varint(&mut out, self.postings.len() as u64);
+        for (trigram, ids) in &self.postings {
+            write_varint(&mut out, *trigram as u64);
+            write_varint(&mut out, ids.len() as u64);
+            let mut previous = 0;
+            for &id in ids {
+                write_varint(&mut out, (id - previous) as u64);
+                previous = id;
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        file_io::atomic_write(path, &out)
+    }
+
+    /// Drop the files whose id is marked in `dead` and renumber the rest
+    fn remove(&mut self, dead: &[bool]) {
+        let mut new_ids = Vec::with_capacity(self.files.len());
+        let mut next = 0;
+        for &is_dead in dead {
+            new_ids.push(next);
+            if !is_dead {
+                next += 1;
+            }
+        }
+
+        for ids in self.postings.values_mut() {
+            ids.retain(|&id| !dead[id as usize]);
+            for id in ids.iter_mut() {
+                *id = new_ids[*id as usize];
+            }
+        }
+        self.postings.retain(|_, ids| !ids.is_empty());
+
+        let mut id = 0;
+        self.files.retain(|_| {
+            id += 1;
+            !dead[id - 1]
+        });
+    }
+
+    /// Bring the index up to date with the files under `roots`.
+    ///
+    /// Only new and changed files are read. `progress` is called with the
+    /// number of files indexed so far and the number to index. Returns
+    /// whether anything changed, and the folders walked.
+    fn refresh<F: Fn(usize, usize)>(
+        &mut self,
+        roots: &[PathBuf],
+        skip: &[String],
+        progress: F,
+    ) -> (bool, HashSet<PathBuf>) {
+        let mut on_disk: HashMap<PathBuf, (u64, u64)> = HashMap::new();
+        let mut dirs = HashSet::new();
+        for root in roots {
+            for entry in walk_workspace(root, skip, false).filter_map(|e| e.ok()) {
+                let Some(file_type) = entry.file_type() else {
+                    continue;
+                };
+                if file_type.is_dir() {
+                    dirs.insert(entry.into_path());
+                    continue;
+                }
+                if !file_type.is_file() {
+                    continue;
+                }
+                if let Ok(metadata) = entry.metadata() {
+                    on_disk.insert(
+                        entry.into_path(),
+                        (file_io::modified_millis(&metadata), metadata.len()),
+                    );
+                }
+            }
+        }
+
+        let dead: Vec<bool> = self
+            .files
+            .iter()
+            .map(|f| on_disk.get(&f.path) != Some(&(f.mtime, f.size)))
+            .collect();
+        let removed = dead.iter().any(|&d| d);
+        if removed {
+            self.remove(&dead);
+        }
+
+        let known: HashSet<&PathBuf> = self.files.iter().map(|f| &f.path).collect();
+        let mut added: Vec<(PathBuf, u64, u64)> = on_disk
+            .into_iter()
+            .filter(|(path, _)| !known.contains(path))
+            .map(|(path, (mtime, size))| (path, mtime, size))
+            .collect();
+        added.sort();
+
+        let total = added.len();
+        self.add(&added, progress);
+        (removed || total > 0, dirs)
+    }
+
+    /// Re-read the indexed files among `paths`, returning whether any of
+    /// them was indexed. Files that are gone are dropped.
+    fn refresh_paths(&mut self, paths: &HashSet<PathBuf>) -> bool {
+        let dead: Vec<bool> = self.files.iter().map(|f| paths.contains(&f.path)).collect();
+        if !dead.contains(&true) {
+            return false;
+        }
+        let mut changed: Vec<(PathBuf, u64, u64)> = self
+            .files
+            .iter()
+            .filter(|f| paths.contains(&f.path))
+            .filter_map(|f| {
+                let metadata = fs::metadata(&f.path).ok().filter(|m| m.is_file())?;
+                let mtime = file_io::modified_millis(&metadata);
+                Some((f.path.clone(), mtime, metadata.len()))
+            })
+            .collect();
+        changed.sort();
+        self.remove(&dead);
+        self.add(&changed, |_, _| {});
+        true
+    }
+
+    /// Index `added`, which must not be in the index yet
+    fn add<F: Fn(usize, usize)>(&mut self, added: &[(PathBuf, u64, u64)], progress: F) {
+        let total = added.len();
+        for (done, chunk) in added.chunks(INDEX_CHUNK_SIZE).enumerate() {
+            let computed: Vec<_> = chunk
+                .par_iter()
+                .map(|(path, _, size)| {
+                    (*size <= MAX_INDEXED_FILE_SIZE).then(|| file_trigrams(path))
+                })
+                .collect();
+
+            for ((path, mtime, size), grams) in chunk.iter().zip(computed) {
+                let id = self.files.len() as u32;
+                self.files.push(IndexedFile {
+                    path: path.clone(),
+                    mtime: *mtime,
+                    size: *size,
+                    indexed: grams.is_some(),
+                });
+                for trigram in grams.unwrap_or_default() {
+                    self.postings.entry(trigram).or_default().push(id);
+                }
+            }
+            progress((done * INDEX_CHUNK_SIZE + chunk.len()).min(total), total);
+        }
+    }
+
+    /// Indexed files that cannot contain all of `required`
+    fn excluded_files(&self, required: &[u32]) -> HashSet<PathBuf> {
+        let mut lists: Vec<&[u32]> = required
+            .iter()
+            .map(|t| self.postings.get(t).map_or(&[][..], |ids| ids.as_slice()))
+            .collect();
+        lists.sort_by_key(|ids| ids.len());
+
+        let mut possible: Vec<u32> = lists.first().map_or(Vec::new(), |ids| ids.to_vec());
+        for ids in &lists[1..] {
+            possible.retain(|id| ids.binary_search(id).is_ok());
+        }
+
+        let mut excluded = HashSet::new();
+        let mut candidates = possible.iter().peekable();
+        for (id, file) in self.files.iter().enumerate() {
+            if candidates.peek() == Some(&&(id as u32)) {
+                candidates.next();
+                continue;
+            }
+            if file.indexed {
+                excluded.insert(file.path.clone());
+            }
+        }
+        excluded
+    }
+}
+
+fn index_path(app: &AppHandle, workspace_id: &str) -> AppResult<PathBuf> {
+    // The id comes from the webview and becomes a file name
+    if workspace_id.is_empty()
+        || !workspace_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(AppError::NotFound(format!(
+            "Unknown workspace: {}",
+            workspace_id
+        )));
+    }
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .join("search-index")
+        .join(format!("{}.idx", workspace_id)))
+}
+
+/// Whether full-text search of a workspace goes through a trigram index
+pub fn is_enabled(workspace: &Workspace) -> bool {
+    workspace
+        .settings
+        .get("searchIndex")
+        .and_then(|v| v.as_bool())
+        == Some(true)
+}
+
+/// What changed on disk since an index was last brought up to date
+#[derive(Default)]
+struct Stale {
+    all: bool,               // files came or went, or events were lost: walk everything
+    paths: HashSet<PathBuf>, // files whose content changed
+}
+
+/// A loaded index and the watcher telling which of its files went stale
+struct LoadedIndex {
+    index: TrigramIndex,
+    stale: Arc<Mutex<Stale>>,
+    watched: HashSet<PathBuf>,
+    watcher: Option<RecommendedWatcher>,
+    updating: bool, // a background update is on its way
+}
+
+impl LoadedIndex {
+    fn new(index: TrigramIndex) -> Self {
+        let stale = Arc::new(Mutex::new(Stale {
+            all: true,
+            paths: HashSet::new(),
+        }));
+        let events = stale.clone();
+
+        // Edits only stale the edited files, anything else needs a walk
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let mut stale = events.lock().unwrap();
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(event)
+                    if matches!(event.kind, EventKind::Modify(kind) if !matches!(kind, ModifyKind::Name(_)))
+                        && !event.need_rescan() =>
+                {
+                    stale.paths.extend(event.paths);
+                }
+                _ => stale.all = true,
+            }
+        });
+        if let Err(e) = &watcher {
+            log::warn!(
+                "File watcher unavailable, searches will not use the index: {}",
+                e
+            );
+        }
+
+        Self {
+            index,
+            stale,
+            watched: HashSet::new(),
+            watcher: watcher.ok(),
+            updating: false,
+        }
+    }
+
+    /// Walk the workspace, update the index and watch every folder walked.
+    ///
+    /// Folders are watched one level at a time, like the quick-open index.
+    fn update(&mut self, app: &AppHandle, workspace: &Workspace, skip: &[String]) -> AppResult<()> {
+        // Changes from here on are picked up next time. Without a watcher
+        // nothing tells when the index goes stale, so it is never trusted.
+        *self.stale.lock().unwrap() = Stale {
+            all: self.watcher.is_none(),
+            paths: HashSet::new(),
+        };
+        let roots: Vec<PathBuf> = workspace.roots.iter().map(PathBuf::from).collect();
+        let (changed, dirs) = self.index.refresh(&roots, skip, |indexed, total| {
+            let _ = app.emit(
+                "search-index-progress",
+                serde_json::json!({ "workspaceId": workspace.id, "indexed": indexed, "total": total }),
+            );
+        });
+        self.updating = false;
+
+        if let Some(watcher) = &mut self.watcher {
+            for gone in self.watched.difference(&dirs) {
+                let _ = watcher.unwatch(gone);
+            }
+            for dir in dirs.difference(&self.watched) {
+                if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("Cannot watch {}: {}", dir.display(), e);
+                }
+            }
+        }
+        self.watched = dirs;
+
+        if changed {
+            self.index.save(&index_path(app, &workspace.id)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Trigram indexes of open workspaces, loaded from disk on first use
+#[derive(Default)]
+pub struct SearchIndexes {
+    loaded: Mutex<HashMap<String, Arc<Mutex<LoadedIndex>>>>,
+}
+
+impl SearchIndexes {
+    fn get(&self, app: &AppHandle, workspace_id: &str) -> Arc<Mutex<LoadedIndex>> {
+        self.loaded
+            .lock()
+            .unwrap()
+            .entry(workspace_id.to_string())
+            .or_insert_with(|| {
+                let saved = index_path(app, workspace_id)
+                    .ok()
+                    .and_then(|path| TrigramIndex::load(&path));
+                Arc::new(Mutex::new(LoadedIndex::new(saved.unwrap_or_default())))
+            })
+            .clone()
+    }
+
+    /// Update the index of a workspace and save it if anything changed.
+    ///
+    /// Progress is emitted as `search-index-progress` events
+    /// (`{ workspaceId, indexed, total }`).
+    pub fn update(&self, app: &AppHandle, workspace: &Workspace, skip: &[String]) -> AppResult<()> {
+        let loaded = self.get(app, &workspace.id);
+        let mut loaded = loaded.lock().unwrap();
+        loaded.update(app, workspace, skip)
+    }
+
+    /// Files of an up-to-date index that cannot match `query`.
+    ///
+    /// Files the watcher saw change are re-read first. When files came or
+    /// went the index is updated in the background and this search goes
+    /// without it. Empty when the query is too short to narrow the search.
+    pub fn excluded_files(
+        &self,
+        app: &AppHandle,
+        workspace: &Workspace,
+        skip: &[String],
+        query: &str,
+        options: &SearchOptions,
+    ) -> AppResult<HashSet<PathBuf>> {
+        let required = required_trigrams(query, options);
+        if required.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let loaded = self.get(app, &workspace.id);
+        let Ok(mut index) = loaded.try_lock() else {
+            return Ok(HashSet::new()); // being updated
+        };
+        let stale = std::mem::take(&mut *index.stale.lock().unwrap());
+        if stale.all {
+            index.stale.lock().unwrap().all = true;
+            if !index.updating {
+                index.updating = true;
+                let (app, workspace, skip) = (app.clone(), workspace.clone(), skip.to_vec());
+                thread::spawn(move || {
+                    let indexes = app.state::<SearchIndexes>();
+                    if let Err(e) = indexes.update(&app, &workspace, &skip) {
+                        log::error!("Failed to index workspace {}: {:?}", workspace.id, e);
+                    }
+                });
+            }
+            return Ok(HashSet::new());
+        }
+
+        if index.index.refresh_paths(&stale.paths) {
+            index.index.save(&index_path(app, &workspace.id)?)?;
+        }
+        Ok(index.index.excluded_files(&required))
+    }
+
+    /// Forget an index and delete it from disk
+    pub fn delete(&self, app: &AppHandle, workspace_id: &str) -> AppResult<()> {
+        let path = index_path(app, workspace_id)?;
+        self.loaded.lock().unwrap().remove(workspace_id);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Unload the index of a closed workspace, keeping it on disk
+    pub fn forget(&self, workspace_id: &str) {
+        self.loaded.lock().unwrap().remove(workspace_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(query: &str, regex: bool, case_sensitive: bool) -> Vec<u32> {
+        let options = SearchOptions {
+            regex,
+            case_sensitive,
+            ..SearchOptions::default()
+        };
+        required_trigrams(query, &options)
+    }
+
+    fn expected(windows: &[&str]) -> Vec<u32> {
+        let mut set: Vec<u32> = windows.iter().map(|w| trigram(w.as_bytes())).collect();
+        set.sort_unstable();
+        set
+    }
+
+    #[test]
+    fn literal_queries_need_all_their_trigrams() {
+        assert_eq!(plan("hello", false, true), expected(&["hel", "ell", "llo"]));
+        // The index is case-folded, so case only matters for the final match
+        assert_eq!(plan("HeLLo", false, false), plan("hello", false, true));
+        // Regex syntax is plain text here
+        assert_eq!(plan("a.b", false, true), expected(&["a.b"]));
+        assert!(plan("ab", false, true).is_empty());
+    }
+
+    #[test]
+    fn regex_queries_need_their_literal_runs() {
+        assert_eq!(plan("foo.*bar", true, true), expected(&["foo", "bar"]));
+        assert_eq!(plan(r"(abcd)+\d?x", true, true), expected(&["abc", "bcd"]));
+        // Optional parts and alternatives are not required
+        assert!(plan("(abc)?", true, true).is_empty());
+        assert!(plan("abc|def", true, true).is_empty());
+        // An invalid pattern is not narrowed, search reports the error
+        assert!(plan("abc(", true, true).is_empty());
+    }
+
+    #[test]
+    fn case_insensitive_queries_skip_non_ascii_trigrams() {
+        assert_eq!(plan("straße", false, false), expected(&["str", "tra"]));
+        let mut sensitive: Vec<u32> = "straße"
+            .as_bytes()
+            .windows(TRIGRAM_LEN)
+            .map(trigram)
+            .collect();
+        sensitive.sort_unstable();
+        assert_eq!(plan("straße", false, true), sensitive);
+        assert!(plan("größe", false, false).is_empty());
+    }
+}
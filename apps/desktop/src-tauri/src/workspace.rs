@@ -0,0 +1,130 @@
+use crate::commands::config::{read_config, write_config};
+use crate::errors::{AppError, AppResult};
+use crate::models::Workspace;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+use tauri::AppHandle;
+
+const WORKSPACES_KEY: &str = "workspaces";
+const RECENT_PROJECTS_KEY: &str = "recent_projects";
+const MAX_RECENT_PROJECTS: usize = 10;
+
+/// Workspaces currently open in the app, keyed by id.
+///
+/// The id is the owner of everything cached per project (detection results,
+/// watchers, search indexes), so those caches are dropped with the workspace.
+#[derive(Default)]
+pub struct WorkspaceManager {
+    open: RwLock<HashMap<String, Workspace>>,
+}
+
+impl WorkspaceManager {
+    pub fn get(&self, id: &str) -> AppResult<Workspace> {
+        self.open
+            .read()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Workspace not open: {}", id)))
+    }
+
+    pub fn list(&self) -> Vec<Workspace> {
+        self.open.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn insert(&self, workspace: Workspace) {
+        self.open
+            .write()
+            .unwrap()
+            .insert(workspace.id.clone(), workspace);
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Workspace> {
+        self.open.write().unwrap().remove(id)
+    }
+
+    /// Apply `f` to an open workspace and return the updated copy
+    pub fn update<F: FnOnce(&mut Workspace)>(&self, id: &str, f: F) -> AppResult<Workspace> {
+        let mut open = self.open.write().unwrap();
+        let workspace = open
+            .get_mut(id)
+            .ok_or_else(|| AppError::NotFound(format!("Workspace not open: {}", id)))?;
+        f(workspace);
+        Ok(workspace.clone())
+    }
+
+    /// The open workspace that contains `path`
+    pub fn containing(&self, path: &Path) -> Option<Workspace> {
+        self.open
+            .read()
+            .unwrap()
+            .values()
+            .find(|w| w.roots.iter().any(|r| path.starts_with(r)))
+            .cloned()
+    }
+
+    /// Settings of the open workspace that contains `path`
+    pub fn settings_for(&self, path: &Path) -> Option<serde_json::Value> {
+        self.containing(path).map(|w| w.settings)
+    }
+
+    /// Whether any open workspace still uses `root`
+    pub fn holds_root(&self, root: &str) -> bool {
+        self.open
+            .read()
+            .unwrap()
+            .values()
+            .any(|w| w.roots.iter().any(|r| r == root))
+    }
+}
+
+/// All workspaces ever opened, as persisted in the config store
+pub fn load_saved(app: &AppHandle) -> Vec<Workspace> {
+    read_config(app, WORKSPACES_KEY)
+        .ok()
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// Insert or replace a workspace in the persisted list
+pub fn save(app: &AppHandle, workspace: &Workspace) -> AppResult<()> {
+    let mut saved = load_saved(app);
+    saved.retain(|w| w.id != workspace.id);
+    saved.push(workspace.clone());
+
+    let value = serde_json::to_value(saved).map_err(|e| AppError::Internal(e.to_string()))?;
+    write_config(app, WORKSPACES_KEY, &value).map_err(AppError::Internal)
+}
+
+pub fn recent_projects(app: &AppHandle) -> Vec<String> {
+    read_config(app, RECENT_PROJECTS_KEY)
+        .ok()
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// Folders the user already opened as workspace roots, from the persisted
+/// workspaces and recent projects. The webview cannot write either list.
+pub fn known_roots(app: &AppHandle) -> Vec<String> {
+    let mut known: Vec<String> = load_saved(app).into_iter().flat_map(|w| w.roots).collect();
+    known.extend(recent_projects(app));
+    known
+}
+
+/// Whether `key` is a config entry that only the backend may write
+pub fn is_reserved_key(key: &str) -> bool {
+    key == WORKSPACES_KEY || key == RECENT_PROJECTS_KEY
+}
+
+/// Move `path` to the front of the recent projects list
+pub fn push_recent_project(app: &AppHandle, path: &str) -> AppResult<Vec<String>> {
+    let mut recent = recent_projects(app);
+    recent.retain(|p| p != path);
+    recent.insert(0, path.to_string());
+    recent.truncate(MAX_RECENT_PROJECTS);
+
+    write_config(app, RECENT_PROJECTS_KEY, &serde_json::json!(recent))
+        .map_err(AppError::Internal)?;
+    Ok(recent)
+}
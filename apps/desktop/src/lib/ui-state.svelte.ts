@@ -1,5 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
-import { open } from "@tauri-apps/plugin-dialog";
+import { listen } from "@tauri-apps/api/event";
+import { ask } from "@tauri-apps/plugin-dialog";
 import { appConsole } from "./stores/console.svelte";
 
 export function createUIState() {
@@ -18,8 +19,9 @@ export function createUIState() {
   let bottomPanelHeight = $state(200);
 
   let projectRoot = $state<string | null>(null);
+  let workspaceId = $state<string | null>(null);
   let recentProjects = $state<string[]>([]);
-  let openTabs = $state<{ id: string; label: string; path: string; content: string; type?: "file" | "settings" | "welcome" }[]>([]);
+  let openTabs = $state<{ id: string; label: string; path: string; content: string; type?: "file" | "settings" | "welcome"; version?: { mtime: number; hash: string } }[]>([]);
   let activeTabId = $state<string | null>(null);
   let commitMessage = $state("");
 
@@ -46,7 +48,13 @@ export function createUIState() {
   let contextMenu = $state({ x: 0, y: 0, visible: false, items: [] as any[] });
   let renamingPath = $state<string | null>(null);
   let fileTree = $state<any>(null);
-  let searchResults = $state<any[]>([]);
+  let searchResults = $state<any[]>([]); // one entry per file with its results
+  let activeSearchId: string | null = null;
+  // Events can arrive before start_search returns its id
+  let earlySearchEvents: any[] = [];
+  let searchSummary = $state<any>(null);
+  let searchRunning = $state(false);
+  let searchIndexProgress = $state<{ indexed: number; total: number } | null>(null);
   let gitChanges = $state<{ staged: any[]; unstaged: any[] }>({ staged: [], unstaged: [] });
   let gitBranch = $state("main");
   let activeTaskId = $state<string | null>(null);
@@ -87,6 +95,17 @@ export function createUIState() {
     });
   }
 
+  function replaceArgs(query: string, replacement: string, include?: string, exclude?: string, options?: object) {
+    return {
+      query,
+      path: projectRoot,
+      include: include && include.trim() ? include : null,
+      exclude: exclude && exclude.trim() ? exclude : null,
+      options: options ?? null,
+      replacement
+    };
+  }
+
   function saveChat() {
     saveConfig("chat_data", {
       chatMessages,
@@ -96,6 +115,7 @@ export function createUIState() {
 
   return {
     async init() {
+      this.listenToSearch().catch(console.error);
       try {
         const ui = await invoke("load_config", { key: "ui_state" }) as any;
         if (ui) {
@@ -118,8 +138,7 @@ export function createUIState() {
         // const root = await invoke("load_config", { key: "project_root" }) as string | null;
         // if (root) this.setProjectRoot(root);
 
-        const recent = await invoke("load_config", { key: "recent_projects" }) as string[] | null;
-        if (recent) recentProjects = recent;
+        recentProjects = await invoke("get_recent_projects") as string[];
 
         const tabs = await invoke("load_config", { key: "open_tabs" }) as any[];
         if (tabs) {
@@ -195,14 +214,22 @@ export function createUIState() {
 
     get projectRoot() { return projectRoot; },
     async setProjectRoot(path: string) {
-      projectRoot = path;
-      saveConfig("project_root", path);
-      recentProjects = [path, ...recentProjects.filter(p => p !== path)].slice(0, 10);
-      saveConfig("recent_projects", recentProjects);
+      let workspace: { id: string; roots: string[] };
+      try {
+        workspace = await invoke("open_workspace", { paths: [path] }) as { id: string; roots: string[] };
+      } catch (err) { console.error("Failed to open workspace:", err); return; }
+      if (workspaceId && workspaceId !== workspace.id) {
+        await invoke("close_workspace", { id: workspaceId }).catch(console.error);
+      }
+      workspaceId = workspace.id;
+      projectRoot = workspace.roots[0];
+      saveConfig("project_root", projectRoot);
+      recentProjects = await invoke("get_recent_projects") as string[];
       await this.refreshFileTree();
       await this.refreshGitStatus();
     },
     get recentProjects() { return recentProjects; },
+    get workspaceId() { return workspaceId; },
 
     get openTabs() { return openTabs; },
     get activeTabId() { return activeTabId; },
@@ -219,8 +246,12 @@ export function createUIState() {
       const existing = openTabs.find(t => t.path === path);
       if (existing) { activeTabId = existing.id; return; }
       try {
-        const content = await invoke("read_file", { path }) as string;
-        const newTab = { id: Math.random().toString(36).substring(2, 11), label: name, path, content, type: "file" as const };
+        const file = await invoke("read_file_ex", { path }) as any;
+        if (file.is_binary) { console.error(`Cannot open binary file: ${path}`); return; }
+        // Saving a partial buffer would cut the file short, so large files are not opened for editing
+        if (file.truncated) { console.error(`File too large to edit: ${path} (${file.size} bytes)`); return; }
+        const version = file.version ?? undefined;
+        const newTab = { id: Math.random().toString(36).substring(2, 11), label: name, path, content: file.content, type: "file" as const, version };
         openTabs.push(newTab);
         activeTabId = newTab.id;
         this.saveTabs();
@@ -278,9 +309,24 @@ export function createUIState() {
     async saveActiveFile() {
       const activeTab = openTabs.find(t => t.id === activeTabId);
       if (activeTab && projectRoot) {
+        const write = (encoding: string | null) => invoke("write_file", {
+          path: activeTab.path,
+          content: activeTab.content,
+          expectedMtime: activeTab.version?.mtime ?? null,
+          expectedHash: activeTab.version?.hash ?? null,
+          encoding
+        }) as Promise<{ mtime: number; hash: string }>;
         try {
-          await invoke("write_file", { path: activeTab.path, content: activeTab.content });
-        } catch (err) { console.error("Failed to save file:", err); }
+          activeTab.version = await write(null);
+        } catch (err: any) {
+          // The file's legacy encoding cannot hold what was typed, offer UTF-8 rather than losing characters
+          const reason: string | undefined = err?.NotSupported;
+          if (reason?.includes("as UTF-8") && await ask(`${reason}.\n\nConvert the file to UTF-8?`, { title: "Save as UTF-8", kind: "warning" })) {
+            try { activeTab.version = await write("utf-8"); } catch (err) { console.error("Failed to save file:", err); }
+          } else {
+            console.error("Failed to save file:", err);
+          }
+        }
       }
     },
     async refreshProject() {
@@ -299,24 +345,125 @@ export function createUIState() {
     },
     async openFolder() {
       try {
-        const selected = await open({ directory: true, multiple: false });
-        if (selected && typeof selected === "string") await this.setProjectRoot(selected);
+        // The backend dialog vouches for the folder, a path from the webview alone is refused
+        const selected = await invoke("pick_workspace_folder") as string | null;
+        if (selected) await this.setProjectRoot(selected);
       } catch (err) { console.error(err); }
     },
     get searchResults() { return searchResults; },
-    async searchProject(query: string, include?: string, exclude?: string) {
+    /** Fuzzy "Go to file" across the workspace, best matches first */
+    async findFiles(query: string, limit?: number) {
+      if (!workspaceId) return [];
+      try {
+        return await invoke("find_files", { workspaceId, query, limit: limit ?? null }) as any[];
+      } catch (err) { console.error(err); return []; }
+    },
+    /** Rust and TypeScript/Svelte symbols matching `query` across the workspace */
+    async searchSymbols(query: string, limit?: number) {
+      if (!workspaceId) return [];
+      try {
+        return await invoke("search_symbols", { workspaceId, query, limit: limit ?? null }) as any[];
+      } catch (err) { console.error(err); return []; }
+    },
+    async documentSymbols(path: string) {
+      try {
+        return await invoke("document_symbols", { path }) as any[];
+      } catch (err) { console.error(err); return []; }
+    },
+    /** Tauri commands with their registration and invoke call sites, plus diagnostics */
+    async analyzeCommands() {
+      if (!workspaceId) return null;
+      try {
+        return await invoke("analyze_commands", { workspaceId }) as any;
+      } catch (err) { console.error(err); return null; }
+    },
+    async searchProject(
+      query: string,
+      include?: string,
+      exclude?: string,
+      options?: { regex?: boolean; case_sensitive?: boolean; whole_word?: boolean; multiline?: boolean; include_ignored?: boolean; search_archives?: boolean; context_lines?: number }
+    ) {
       if (!projectRoot || !query) return;
       try {
-        searchResults = await invoke("search_in_files", { 
+        if (activeSearchId) await invoke("cancel_search", { searchId: activeSearchId });
+        searchResults = [];
+        searchSummary = null;
+        searchRunning = true;
+        activeSearchId = null;
+        earlySearchEvents = [];
+        const searchId = await invoke("start_search", { 
           query, 
           path: projectRoot, 
           include: include && include.trim() ? include : null, 
-          exclude: exclude && exclude.trim() ? exclude : null 
-        });
+          exclude: exclude && exclude.trim() ? exclude : null,
+          options: options ?? null
+        }) as string;
+        activeSearchId = searchId;
+        const early = earlySearchEvents.filter((e) => e.searchId === searchId);
+        earlySearchEvents = [];
+        for (const payload of early) this.handleSearchEvent(payload);
         if (activeActivityId !== "search") { activeActivityId = "search"; activeSidePanelTitle = "SEARCH"; saveUIState(); }
-      } catch (err) { console.error(err); }
+      } catch (err) { console.error(err); searchRunning = false; }
+    },
+    /** Preview a project-wide replace without writing anything. Returns the files with their matches and diffs. */
+    async previewReplace(
+      query: string,
+      replacement: string,
+      include?: string,
+      exclude?: string,
+      options?: { regex?: boolean; case_sensitive?: boolean; whole_word?: boolean; multiline?: boolean }
+    ) {
+      if (!projectRoot || !query) return null;
+      try {
+        const preview = await invoke("replace_in_files", { ...replaceArgs(query, replacement, include, exclude, options), selection: null }) as any;
+        return preview.files as any[];
+      } catch (err) { console.error("Replace preview failed:", err); return null; }
+    },
+    /** Apply the previewed matches picked in `selection` ({ file, hash, match_ids }). Returns the undo id. */
+    async applyReplace(
+      query: string,
+      replacement: string,
+      include: string | undefined,
+      exclude: string | undefined,
+      options: { regex?: boolean; case_sensitive?: boolean; whole_word?: boolean; multiline?: boolean } | undefined,
+      selection: { file: string; hash: string; match_ids: number[] }[]
+    ) {
+      if (!projectRoot || !query) return null;
+      try {
+        const applied = await invoke("replace_in_files", { ...replaceArgs(query, replacement, include, exclude, options), selection }) as any;
+        return applied.undo_id as string | null;
+      } catch (err) { console.error("Replace failed:", err); return null; }
+    },
+    async undoReplace(undoId: string) {
+      try { return await invoke("undo_replace", { undoId }) as string[]; }
+      catch (err) { console.error("Undo replace failed:", err); return null; }
+    },
+    get searchSummary() { return searchSummary; },
+    get searchRunning() { return searchRunning; },
+    get searchIndexProgress() { return searchIndexProgress; },
+    /** Subscribe to streamed search events, results of stale searches are dropped */
+    async listenToSearch() {
+      const onEvent = (event: any) => {
+        if (activeSearchId === null) earlySearchEvents.push(event.payload);
+        else this.handleSearchEvent(event.payload);
+      };
+      await listen("search-result", onEvent);
+      await listen("search-complete", onEvent);
+      await listen("search-index-progress", (event: any) => {
+        if (event.payload.workspaceId !== workspaceId) return;
+        const { indexed, total } = event.payload;
+        searchIndexProgress = indexed < total ? { indexed, total } : null;
+      });
+    },
+    handleSearchEvent(payload: any) {
+      if (payload.searchId !== activeSearchId) return;
+      if (payload.files) searchResults = [...searchResults, ...payload.files];
+      if (payload.summary) { searchSummary = payload.summary; searchRunning = false; }
     },
     clearSearchResults() {
+      if (activeSearchId) invoke("cancel_search", { searchId: activeSearchId }).catch(console.error);
+      activeSearchId = null;
+      searchRunning = false;
       searchResults = [];
     },
     get gitChanges() { return gitChanges; },